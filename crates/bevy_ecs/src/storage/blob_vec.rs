@@ -105,6 +105,13 @@ impl BlobVec {
         self.item_layout
     }
 
+    /// Returns the drop function for values stored in the vector,
+    /// or `None` if they don't need to be dropped.
+    #[inline]
+    pub fn get_drop(&self) -> Option<unsafe fn(OwningPtr<'_>)> {
+        self.drop
+    }
+
     /// Reserves the minimum capacity for at least `additional` more elements to be inserted in the given `BlobVec`.
     /// After calling `reserve_exact`, capacity will be greater than or equal to `self.len() + additional`. Does nothing if
     /// the capacity is already sufficient.
@@ -181,6 +188,36 @@ impl BlobVec {
         self.capacity = new_capacity;
     }
 
+    /// Shrinks the capacity of the vector as much as possible, down to its length.
+    pub fn shrink_to_fit(&mut self) {
+        // ZST `BlobVec`s never allocate, so their capacity stays `usize::MAX`.
+        if self.item_layout.size() == 0 || self.capacity == self.len {
+            return;
+        }
+        let old_layout =
+            array_layout(&self.item_layout, self.capacity).expect("array layout should be valid");
+        if self.len == 0 {
+            // SAFETY:
+            // - ptr was allocated via this allocator with `old_layout`
+            // - `capacity > len`, so `old_layout` has a non-zero size and the ptr is not dangling
+            unsafe { std::alloc::dealloc(self.get_ptr_mut().as_ptr(), old_layout) };
+            let align = NonZeroUsize::new(self.item_layout.align()).expect("alignment must be > 0");
+            self.data = bevy_ptr::dangling_with_align(align);
+        } else {
+            let new_layout =
+                array_layout(&self.item_layout, self.len).expect("array layout should be valid");
+            // SAFETY:
+            // - ptr was allocated via this allocator with `old_layout`
+            // - `item_layout.size() > 0` and `len > 0`, so the new size is non-zero
+            // - the new size is smaller than the old one, so it cannot overflow
+            let new_data = unsafe {
+                std::alloc::realloc(self.get_ptr_mut().as_ptr(), old_layout, new_layout.size())
+            };
+            self.data = NonNull::new(new_data).unwrap_or_else(|| handle_alloc_error(new_layout));
+        }
+        self.capacity = self.len;
+    }
+
     /// Initializes the value at `index` to `value`. This function does not do any bounds checking.
     ///
     /// # Safety
@@ -652,6 +689,75 @@ mod tests {
         let _ = unsafe { BlobVec::new(item_layout, Some(drop), 0) };
     }
 
+    #[test]
+    fn blob_vec_shrink_to_fit_empty() {
+        // SAFETY: `drop` fn is `None`, usize doesn't need dropping
+        let mut blob_vec = unsafe { BlobVec::new(Layout::new::<usize>(), None, 64) };
+        blob_vec.shrink_to_fit();
+        assert_eq!(blob_vec.capacity(), 0);
+
+        // The vector grows again from the released allocation.
+        // SAFETY: `7` is a usize, i.e. the type corresponding to `item_layout`, and index 0 is in
+        // range once it's pushed
+        unsafe {
+            push(&mut blob_vec, 7usize);
+            assert_eq!(get_mut::<usize>(&mut blob_vec, 0), &7);
+        }
+    }
+
+    #[test]
+    fn blob_vec_shrink_to_fit_partial() {
+        let drop_counter = Rc::new(RefCell::new(0));
+        {
+            let foos: Vec<_> = (0..3)
+                .map(|a| Foo {
+                    a,
+                    b: a.to_string(),
+                    drop_counter: drop_counter.clone(),
+                })
+                .collect();
+            // SAFETY: drop is able to drop a value of its `item_layout`
+            let mut blob_vec =
+                unsafe { BlobVec::new(Layout::new::<Foo>(), Some(drop_ptr::<Foo>), 8) };
+            // SAFETY: the following code only deals with values of type `Foo`, which matches the
+            // blob vec's `item_layout`. Every index is in range.
+            unsafe {
+                for foo in &foos {
+                    push(&mut blob_vec, foo.clone());
+                }
+                blob_vec.shrink_to_fit();
+                assert_eq!(blob_vec.len(), 3);
+                assert_eq!(blob_vec.capacity(), 3);
+                for (index, foo) in foos.iter().enumerate() {
+                    assert_eq!(get_mut::<Foo>(&mut blob_vec, index), foo);
+                }
+
+                // Already at capacity, so this does nothing.
+                blob_vec.shrink_to_fit();
+                assert_eq!(blob_vec.capacity(), 3);
+            }
+        }
+
+        // The values moved by the reallocation are dropped once, along with the originals.
+        assert_eq!(*drop_counter.borrow(), 6);
+    }
+
+    #[test]
+    fn blob_vec_shrink_to_fit_zst() {
+        // SAFETY: no drop is correct drop for `()`.
+        let mut blob_vec = unsafe { BlobVec::new(Layout::new::<()>(), None, 0) };
+        for _ in 0..2 {
+            OwningPtr::make((), |ptr| {
+                // SAFETY: `BlobVec` was initialized for `()`, so it is safe to push `()` to it.
+                unsafe { blob_vec.push(ptr) };
+            });
+        }
+
+        blob_vec.shrink_to_fit();
+        assert_eq!(blob_vec.len(), 2);
+        assert_eq!(blob_vec.capacity(), usize::MAX);
+    }
+
     #[test]
     #[should_panic(expected = "capacity overflow")]
     fn blob_vec_zst_size_overflow() {
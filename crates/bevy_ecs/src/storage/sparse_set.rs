@@ -1,39 +1,75 @@
 use crate::{
-    component::{ComponentId, ComponentInfo, ComponentTicks, Tick, TickCells},
+    component::{Component, ComponentId, ComponentInfo, ComponentTicks, Tick, TickCells},
     entity::Entity,
     storage::{Column, TableRow},
 };
-use bevy_ptr::{OwningPtr, Ptr};
-use nonmax::NonMaxUsize;
-use std::{cell::UnsafeCell, hash::Hash, marker::PhantomData};
+use bevy_ptr::{OwningPtr, Ptr, UnsafeCellDeref};
+use fixedbitset::FixedBitSet;
+use nonmax::{NonMaxU32, NonMaxUsize};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+use std::{
+    alloc::Layout,
+    cell::UnsafeCell,
+    collections::TryReserveError,
+    hash::Hash,
+    marker::PhantomData,
+    ops::{Bound, Index, IndexMut, RangeBounds},
+};
+use thiserror::Error;
 
 type EntityIndex = u32;
 
-#[derive(Debug)]
-pub(crate) struct SparseArray<I, V = I> {
-    values: Vec<Option<V>>,
+/// The number of slots in each page of a [`SparseArray`].
+const SPARSE_ARRAY_PAGE_LEN: usize = 1024;
+
+/// Splits the slot of a sparse array with pages of `PAGE` slots into the index of its page and
+/// its offset within that page.
+#[inline]
+fn page_and_offset<const PAGE: usize>(slot: usize) -> (usize, usize) {
+    debug_assert!(
+        PAGE.is_power_of_two(),
+        "page size {PAGE} is not a power of two"
+    );
+    (slot / PAGE, slot % PAGE)
+}
+
+/// A [`PagedSparseArray`] with pages of 1024 slots, as used by [`SparseSet`].
+pub(crate) type SparseArray<I, V = I> = PagedSparseArray<I, V, SPARSE_ARRAY_PAGE_LEN>;
+
+/// A growable array of optional values, indexed by a [`SparseSetIndex`].
+///
+/// The slots are split into pages of `PAGE`, and each page only grows as far as the largest index
+/// stored in it. A range of indices that holds no values therefore costs a single empty page per
+/// `PAGE` indices rather than a slot per index, which keeps memory bounded when the indices in use
+/// are few but large. Smaller pages waste less memory on partially filled pages, while larger pages
+/// need fewer allocations. `PAGE` must be a power of two.
+#[derive(Debug, Clone)]
+pub struct PagedSparseArray<I, V, const PAGE: usize = 1024> {
+    pages: Vec<Vec<Option<V>>>,
     marker: PhantomData<I>,
 }
 
-/// A space-optimized version of [`SparseArray`] that cannot be changed
+/// A space-optimized version of [`PagedSparseArray`] that cannot be changed
 /// after construction.
-#[derive(Debug)]
-pub(crate) struct ImmutableSparseArray<I, V = I> {
-    values: Box<[Option<V>]>,
+#[derive(Debug, Clone)]
+pub(crate) struct ImmutableSparseArray<I, V = I, const PAGE: usize = SPARSE_ARRAY_PAGE_LEN> {
+    pages: Box<[Box<[Option<V>]>]>,
     marker: PhantomData<I>,
 }
 
-impl<I: SparseSetIndex, V> Default for SparseArray<I, V> {
+impl<I, V, const PAGE: usize> Default for PagedSparseArray<I, V, PAGE> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<I, V> SparseArray<I, V> {
+impl<I, V, const PAGE: usize> PagedSparseArray<I, V, PAGE> {
+    /// Creates a new, empty [`PagedSparseArray`].
     #[inline]
     pub const fn new() -> Self {
         Self {
-            values: Vec::new(),
+            pages: Vec::new(),
             marker: PhantomData,
         }
     }
@@ -41,12 +77,11 @@ impl<I, V> SparseArray<I, V> {
 
 macro_rules! impl_sparse_array {
     ($ty:ident) => {
-        impl<I: SparseSetIndex, V> $ty<I, V> {
+        impl<I: SparseSetIndex, V, const PAGE: usize> $ty<I, V, PAGE> {
             /// Returns `true` if the collection contains a value for the specified `index`.
             #[inline]
             pub fn contains(&self, index: I) -> bool {
-                let index = index.sparse_set_index();
-                self.values.get(index).map(|v| v.is_some()).unwrap_or(false)
+                self.get(index).is_some()
             }
 
             /// Returns a reference to the value at `index`.
@@ -54,39 +89,329 @@ macro_rules! impl_sparse_array {
             /// Returns `None` if `index` does not have a value or if `index` is out of bounds.
             #[inline]
             pub fn get(&self, index: I) -> Option<&V> {
-                let index = index.sparse_set_index();
-                self.values.get(index).map(|v| v.as_ref()).unwrap_or(None)
+                let (page, offset) = page_and_offset::<PAGE>(index.sparse_set_index());
+                self.pages.get(page)?.get(offset)?.as_ref()
             }
         }
     };
 }
 
-impl_sparse_array!(SparseArray);
+impl_sparse_array!(PagedSparseArray);
 impl_sparse_array!(ImmutableSparseArray);
 
-impl<I: SparseSetIndex, V> SparseArray<I, V> {
+impl<I: SparseSetIndex, V, const PAGE: usize> PagedSparseArray<I, V, PAGE> {
+    /// Returns the slot at `slot`, enlarging its page to accommodate it if needed.
+    #[inline]
+    fn slot_mut(&mut self, slot: usize) -> &mut Option<V> {
+        // Filling every slot up to `slot` must not overflow the allocation size, which would
+        // abort in the allocator instead of reporting the offending index.
+        debug_assert!(
+            slot < isize::MAX as usize / std::mem::size_of::<Option<V>>().max(1),
+            "sparse set index {slot} is too large to be stored in a sparse array"
+        );
+        let (page, offset) = page_and_offset::<PAGE>(slot);
+        if page >= self.pages.len() {
+            self.pages.resize_with(page + 1, Vec::new);
+        }
+        let values = &mut self.pages[page];
+        if offset >= values.len() {
+            values.resize_with(offset + 1, || None);
+        }
+        &mut values[offset]
+    }
+
     /// Inserts `value` at `index` in the array.
     ///
     /// If `index` is out-of-bounds, this will enlarge the buffer to accommodate it.
     #[inline]
     pub fn insert(&mut self, index: I, value: V) {
-        let index = index.sparse_set_index();
-        if index >= self.values.len() {
-            self.values.resize_with(index + 1, || None);
+        *self.slot_mut(index.sparse_set_index()) = Some(value);
+    }
+
+    /// Returns a reference to the value at each of `indices`, in the same order.
+    ///
+    /// Each element is `None` if its index does not have a value. Indices may repeat.
+    #[inline]
+    pub fn get_disjoint<const N: usize>(&self, indices: [I; N]) -> [Option<&V>; N] {
+        indices.map(|index| self.get(index))
+    }
+
+    /// Returns an iterator over the [`sparse_set_index`](SparseSetIndex::sparse_set_index)
+    /// and value of every index that has a value, in ascending order of index.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (usize, &V)> + '_ {
+        self.pages.iter().enumerate().flat_map(|(page, values)| {
+            values
+                .iter()
+                .enumerate()
+                .filter_map(move |(offset, value)| Some((page * PAGE + offset, value.as_ref()?)))
+        })
+    }
+
+    /// Returns a mutable reference to the value at `index`.
+    ///
+    /// Returns `None` if `index` does not have a value or if `index` is out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: I) -> Option<&mut V> {
+        let (page, offset) = page_and_offset::<PAGE>(index.sparse_set_index());
+        self.pages.get_mut(page)?.get_mut(offset)?.as_mut()
+    }
+
+    /// Enlarges the buffer so that every index up to `index` is in bounds, without storing a value.
+    ///
+    /// Every page below the one holding `index` is filled to its full length.
+    #[inline]
+    pub fn grow_to_include(&mut self, index: I) {
+        let slot = index.sparse_set_index();
+        self.slot_mut(slot);
+        let (page, _) = page_and_offset::<PAGE>(slot);
+        for values in &mut self.pages[..page] {
+            values.resize_with(PAGE, || None);
+        }
+    }
+
+    /// Removes and returns the value stored at `index`.
+    ///
+    /// Returns `None` if `index` did not have a value or if `index` is out of bounds.
+    #[inline]
+    pub fn remove(&mut self, index: I) -> Option<V> {
+        let (page, offset) = page_and_offset::<PAGE>(index.sparse_set_index());
+        self.pages.get_mut(page)?.get_mut(offset)?.take()
+    }
+
+    /// Removes all of the values stored within.
+    pub fn clear(&mut self) {
+        for values in &mut self.pages {
+            values.clear();
+        }
+    }
+
+    /// Truncates each page past its highest occupied index, drops the trailing pages that hold
+    /// no values and releases the unused capacity.
+    pub fn shrink(&mut self) {
+        for values in &mut self.pages {
+            let len = values
+                .iter()
+                .rposition(Option::is_some)
+                .map_or(0, |offset| offset + 1);
+            values.truncate(len);
+            values.shrink_to_fit();
+        }
+        let len = self
+            .pages
+            .iter()
+            .rposition(|values| !values.is_empty())
+            .map_or(0, |page| page + 1);
+        self.pages.truncate(len);
+        self.pages.shrink_to_fit();
+    }
+
+    /// Returns the number of slots in the array, including the ones without a value.
+    pub(crate) fn len(&self) -> usize {
+        self.pages.iter().map(Vec::len).sum()
+    }
+
+    /// Returns the number of bytes allocated for the pages and their slots.
+    fn heap_bytes(&self) -> usize {
+        let slots: usize = self.pages.iter().map(Vec::capacity).sum();
+        self.pages.capacity() * std::mem::size_of::<Vec<Option<V>>>()
+            + slots * std::mem::size_of::<Option<V>>()
+    }
+
+    /// Returns a copy of every page of the array, for restoring later with
+    /// [`restore`](Self::restore).
+    ///
+    /// Like the array itself, each page is only copied as far as the largest index stored in it.
+    pub fn snapshot(&self) -> Box<[Box<[Option<V>]>]>
+    where
+        V: Clone,
+    {
+        self.pages.iter().map(|values| values[..].into()).collect()
+    }
+
+    /// Overwrites the contents of the array with a `snapshot` taken by [`snapshot`](Self::snapshot).
+    ///
+    /// The existing pages are reused, so restoring does not allocate unless the snapshot reaches
+    /// past their current capacity.
+    pub fn restore(&mut self, snapshot: &[Box<[Option<V>]>])
+    where
+        V: Clone,
+    {
+        self.clear();
+        if snapshot.len() > self.pages.len() {
+            self.pages.resize_with(snapshot.len(), Vec::new);
+        }
+        for (values, page) in self.pages.iter_mut().zip(snapshot.iter()) {
+            values.extend_from_slice(page);
         }
-        self.values[index] = Some(value);
+    }
+
+    /// Returns an iterator over the differences between this array and `previous`, in ascending
+    /// order of index.
+    pub fn diff<'a>(
+        &'a self,
+        previous: &'a PagedSparseArray<I, V, PAGE>,
+    ) -> impl Iterator<Item = SparseArrayChange<'a, I, V>>
+    where
+        V: PartialEq,
+    {
+        let pages = self.pages.len().max(previous.pages.len());
+        (0..pages).flat_map(move |page| {
+            let current = self.pages.get(page).map_or(&[][..], Vec::as_slice);
+            let previous = previous.pages.get(page).map_or(&[][..], Vec::as_slice);
+            let len = current.len().max(previous.len());
+            (0..len).filter_map(move |offset| {
+                let current = current.get(offset).and_then(Option::as_ref);
+                let previous = previous.get(offset).and_then(Option::as_ref);
+                let index = I::get_sparse_set_index(page * PAGE + offset);
+                match (previous, current) {
+                    (None, Some(value)) => Some(SparseArrayChange::Added { index, value }),
+                    (Some(previous), None) => Some(SparseArrayChange::Removed { index, previous }),
+                    (Some(previous), Some(value)) if previous != value => {
+                        Some(SparseArrayChange::Changed {
+                            index,
+                            previous,
+                            value,
+                        })
+                    }
+                    _ => None,
+                }
+            })
+        })
+    }
+
+    /// Returns a [`PresenceMask`] of the indices that currently have a value.
+    pub fn presence_mask(&self) -> PresenceMask<I> {
+        let mut mask = PresenceMask::new();
+        for (slot, _) in self.iter() {
+            mask.bits.grow(slot + 1);
+            mask.bits.insert(slot);
+        }
+        mask
+    }
+
+    /// Converts the [`PagedSparseArray`] into an immutable variant.
+    ///
+    /// Each page is trimmed to its highest occupied index, and the trailing pages that hold no
+    /// values are dropped.
+    pub(crate) fn into_immutable(mut self) -> ImmutableSparseArray<I, V, PAGE> {
+        self.shrink();
+        ImmutableSparseArray {
+            pages: self.pages.into_iter().map(Vec::into_boxed_slice).collect(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// A difference at a single index between two [`PagedSparseArray`]s, as returned by
+/// [`PagedSparseArray::diff`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum SparseArrayChange<'a, I, V> {
+    /// The index has a value that it did not have before.
+    Added {
+        /// The index of the value.
+        index: I,
+        /// The new value.
+        value: &'a V,
+    },
+    /// The index no longer has a value.
+    Removed {
+        /// The index of the value.
+        index: I,
+        /// The value that was removed.
+        previous: &'a V,
+    },
+    /// The value of the index is different.
+    Changed {
+        /// The index of the value.
+        index: I,
+        /// The old value.
+        previous: &'a V,
+        /// The new value.
+        value: &'a V,
+    },
+}
+
+/// A variant of a sparse array for [`Copy`] values that marks empty slots with a sentinel
+/// value instead of wrapping every slot in an [`Option`].
+///
+/// This avoids the space taken by the [`Option`] discriminant, at the cost of the sentinel
+/// itself never being storable.
+#[derive(Debug, Clone)]
+pub struct CompactSparseArray<I, V: Copy + PartialEq> {
+    values: Vec<V>,
+    sentinel: V,
+    marker: PhantomData<I>,
+}
+
+impl<I, V: Copy + PartialEq> CompactSparseArray<I, V> {
+    /// Creates a new [`CompactSparseArray`] that uses `sentinel` to mark empty slots.
+    #[inline]
+    pub const fn new(sentinel: V) -> Self {
+        Self {
+            values: Vec::new(),
+            sentinel,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the value used to mark empty slots.
+    #[inline]
+    pub fn sentinel(&self) -> V {
+        self.sentinel
+    }
+}
+
+impl<I: SparseSetIndex, V: Copy + PartialEq> CompactSparseArray<I, V> {
+    /// Returns `true` if the collection contains a value for the specified `index`.
+    #[inline]
+    pub fn contains(&self, index: I) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Returns a reference to the value at `index`.
+    ///
+    /// Returns `None` if `index` does not have a value or if `index` is out of bounds.
+    #[inline]
+    pub fn get(&self, index: I) -> Option<&V> {
+        let index = index.sparse_set_index();
+        self.values
+            .get(index)
+            .filter(|value| **value != self.sentinel)
     }
 
     /// Returns a mutable reference to the value at `index`.
     ///
     /// Returns `None` if `index` does not have a value or if `index` is out of bounds.
+    ///
+    /// Unlike [`insert`](Self::insert), nothing stops the [sentinel](Self::sentinel) from being
+    /// written through the returned reference, which empties the slot as if the value had been
+    /// [removed](Self::remove).
     #[inline]
     pub fn get_mut(&mut self, index: I) -> Option<&mut V> {
         let index = index.sparse_set_index();
+        let sentinel = self.sentinel;
         self.values
             .get_mut(index)
-            .map(|v| v.as_mut())
-            .unwrap_or(None)
+            .filter(|value| **value != sentinel)
+    }
+
+    /// Inserts `value` at `index` in the array.
+    ///
+    /// If `index` is out-of-bounds, this will enlarge the buffer to accommodate it.
+    ///
+    /// # Panics
+    /// Panics if `value` is the sentinel.
+    #[inline]
+    pub fn insert(&mut self, index: I, value: V) {
+        assert!(
+            value != self.sentinel,
+            "cannot insert the sentinel value into a CompactSparseArray"
+        );
+        let index = index.sparse_set_index();
+        if index >= self.values.len() {
+            self.values.resize(index + 1, self.sentinel);
+        }
+        self.values[index] = value;
     }
 
     /// Removes and returns the value stored at `index`.
@@ -95,26 +420,80 @@ impl<I: SparseSetIndex, V> SparseArray<I, V> {
     #[inline]
     pub fn remove(&mut self, index: I) -> Option<V> {
         let index = index.sparse_set_index();
-        self.values.get_mut(index).and_then(Option::take)
+        let slot = self.values.get_mut(index)?;
+        let value = std::mem::replace(slot, self.sentinel);
+        (value != self.sentinel).then_some(value)
     }
 
     /// Removes all of the values stored within.
     pub fn clear(&mut self) {
         self.values.clear();
     }
+}
 
-    /// Converts the [`SparseArray`] into an immutable variant.
-    pub(crate) fn into_immutable(self) -> ImmutableSparseArray<I, V> {
-        ImmutableSparseArray {
-            values: self.values.into_boxed_slice(),
+/// A set of [`SparseSetIndex`] values stored as a bit mask.
+///
+/// This tracks which indices are present separately from any values associated with them, such
+/// as a snapshot of the occupied slots of a [`PagedSparseArray`] taken with
+/// [`PagedSparseArray::presence_mask`].
+#[derive(Debug, Clone)]
+pub struct PresenceMask<I> {
+    bits: FixedBitSet,
+    marker: PhantomData<I>,
+}
+
+impl<I> Default for PresenceMask<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> PresenceMask<I> {
+    /// Creates a new, empty [`PresenceMask`].
+    pub fn new() -> Self {
+        Self {
+            bits: FixedBitSet::new(),
             marker: PhantomData,
         }
     }
 }
 
+impl<I: SparseSetIndex> PresenceMask<I> {
+    /// Marks `index` as present.
+    pub fn insert(&mut self, index: I) {
+        let index = index.sparse_set_index();
+        self.bits.grow(index + 1);
+        self.bits.insert(index);
+    }
+
+    /// Marks `index` as absent.
+    pub fn remove(&mut self, index: I) {
+        let index = index.sparse_set_index();
+        if index < self.bits.len() {
+            self.bits.set(index, false);
+        }
+    }
+
+    /// Returns `true` if `index` is present.
+    #[inline]
+    pub fn contains(&self, index: I) -> bool {
+        self.bits.contains(index.sparse_set_index())
+    }
+
+    /// Returns an iterator over the present indices, in ascending order.
+    pub fn iter_present(&self) -> impl Iterator<Item = I> + '_ {
+        self.bits.ones().map(I::get_sparse_set_index)
+    }
+}
+
 /// A sparse data structure of [`Component`](crate::component::Component)s.
 ///
 /// Designed for relatively fast insertions and deletions.
+///
+/// Component values are stored contiguously with no gaps, so that they can be iterated and
+/// accessed as a slice. Removing a value moves the last one into its place, and the freed
+/// capacity is reused by the next insertion, so repeatedly removing and re-inserting components
+/// does not allocate.
 #[derive(Debug)]
 pub struct ComponentSparseSet {
     dense: Column,
@@ -139,7 +518,25 @@ impl ComponentSparseSet {
         }
     }
 
+    /// Returns `true` if values of the component described by `component_info` can be stored in
+    /// this sparse set, that is, if they have the same layout and drop function.
+    pub(crate) fn layout_matches(&self, component_info: &ComponentInfo) -> bool {
+        self.dense.item_layout() == component_info.layout()
+            && self.get_drop().map(|drop| drop as usize)
+                == component_info.drop().map(|drop| drop as usize)
+    }
+
+    /// Returns the drop function for the component values in this sparse set,
+    /// or `None` if they don't need to be dropped.
+    #[inline]
+    pub fn get_drop(&self) -> Option<unsafe fn(OwningPtr<'_>)> {
+        self.dense.get_drop()
+    }
+
     /// Removes all of the values stored within.
+    ///
+    /// The allocations are kept, so refilling the sparse set up to its previous size does not
+    /// reallocate. Follow up with [`shrink_to_fit`](Self::shrink_to_fit) to release them as well.
     pub(crate) fn clear(&mut self) {
         self.dense.clear();
         self.entities.clear();
@@ -158,6 +555,80 @@ impl ComponentSparseSet {
         self.dense.len() == 0
     }
 
+    /// Returns the number of component values the sparse set can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.dense.capacity()
+    }
+
+    /// Shrinks the storage of the component values and their entities to fit the number of
+    /// values, and the sparse index to fit the highest entity index currently stored.
+    pub fn shrink_to_fit(&mut self) {
+        self.dense.shrink_to_fit();
+        self.entities.shrink_to_fit();
+        self.sparse.shrink();
+    }
+
+    /// Iterates over the [index](Entity::index) of each entity with a component value, in the
+    /// same order as the values are stored.
+    ///
+    /// Only indices are returned, as entity generations are not stored in release builds.
+    pub fn iter_entities(&self) -> impl Iterator<Item = u32> + '_ {
+        self.entities.iter().map(|entity| {
+            #[cfg(not(debug_assertions))]
+            let index = *entity;
+            #[cfg(debug_assertions)]
+            let index = entity.index();
+            index
+        })
+    }
+
+    /// Iterates over the [index](Entity::index) of each entity whose component value was changed
+    /// since `last_run`, as seen from `this_run`, paired with the value.
+    ///
+    /// See [`iter_entities`](Self::iter_entities) for why only indices are returned.
+    pub fn iter_changed_since(
+        &self,
+        last_run: Tick,
+        this_run: Tick,
+    ) -> impl Iterator<Item = (u32, Ptr<'_>)> + '_ {
+        self.iter_entities()
+            .enumerate()
+            .filter_map(move |(dense_index, index)| {
+                let dense_index = TableRow::from_usize(dense_index);
+                // SAFETY: `dense_index` is within the range of the values, which lines up with
+                // the entities.
+                unsafe {
+                    let changed = self.dense.get_changed_tick_unchecked(dense_index).read();
+                    changed
+                        .is_newer_than(last_run, this_run)
+                        .then(|| (index, self.dense.get_data_unchecked(dense_index)))
+                }
+            })
+    }
+
+    /// Converts the sparse set into a [`FrozenComponentSparseSet`], which can only be read from.
+    pub fn freeze(self) -> FrozenComponentSparseSet {
+        FrozenComponentSparseSet {
+            dense: self.dense,
+            entities: self.entities.into_boxed_slice(),
+            sparse: self.sparse.into_immutable(),
+        }
+    }
+
+    /// Returns the number of bytes allocated for the component values and their ticks, the
+    /// entities, and the sparse index.
+    pub fn heap_bytes(&self) -> usize {
+        #[cfg(not(debug_assertions))]
+        let entity_size = std::mem::size_of::<EntityIndex>();
+        #[cfg(debug_assertions)]
+        let entity_size = std::mem::size_of::<Entity>();
+        self.dense.capacity() * self.dense.item_layout().size()
+            + self.dense.ticks_heap_bytes()
+            + self.entities.capacity() * entity_size
+            + self.sparse.heap_bytes()
+    }
+
     /// Inserts the `entity` key and component `value` pair into this sparse
     /// set.
     ///
@@ -175,12 +646,11 @@ impl ComponentSparseSet {
             assert_eq!(entity, self.entities[dense_index.as_usize()]);
             self.dense.replace(dense_index, value, change_tick);
         } else {
-            let dense_index = self.dense.len();
+            let dense_index = next_table_row(self.dense.len());
             self.dense.push(value, ComponentTicks::new(change_tick));
-            self.sparse
-                .insert(entity.index(), TableRow::from_usize(dense_index));
+            self.sparse.insert(entity.index(), dense_index);
             #[cfg(debug_assertions)]
-            assert_eq!(self.entities.len(), dense_index);
+            assert_eq!(self.entities.len(), dense_index.as_usize());
             #[cfg(not(debug_assertions))]
             self.entities.push(entity.index());
             #[cfg(debug_assertions)]
@@ -327,21 +797,170 @@ impl ComponentSparseSet {
     pub(crate) fn check_change_ticks(&mut self, change_tick: Tick) {
         self.dense.check_change_ticks(change_tick);
     }
+
+    /// Returns an iterator over the index of each entity in the sparse set paired with a typed
+    /// reference to its component value.
+    ///
+    /// # Safety
+    /// - `T` must be the component type this sparse set was created for.
+    /// - No mutable references to any of the component values may exist while the returned
+    ///   iterator or the references it yields are alive.
+    pub unsafe fn typed_iter<T: Component>(&self) -> impl Iterator<Item = (u32, &T)> {
+        debug_assert_eq!(self.dense.item_layout(), Layout::new::<T>());
+        self.entities
+            .iter()
+            .zip(self.dense.get_data_slice::<T>())
+            .map(|(entity, value)| {
+                #[cfg(not(debug_assertions))]
+                let index = *entity;
+                #[cfg(debug_assertions)]
+                let index = entity.index();
+                // SAFETY: the caller guarantees no mutable references to the values exist
+                (index, unsafe { value.deref() })
+            })
+    }
+
+    /// Returns the fraction of `total_entities` that have a component value in this sparse set.
+    ///
+    /// Returns `0.0` if `total_entities` is zero.
+    #[inline]
+    pub fn density(&self, total_entities: usize) -> f32 {
+        if total_entities == 0 {
+            0.0
+        } else {
+            self.len() as f32 / total_entities as f32
+        }
+    }
+
+    /// Returns `true` if the [`density`](Self::density) of this sparse set exceeds `threshold`.
+    ///
+    /// Components present on nearly every entity are better served by [`Table`](crate::storage::Table)
+    /// storage, so this can be used by the storage layer to decide when to migrate them.
+    #[inline]
+    pub fn should_migrate_to_table(&self, total_entities: usize, threshold: f32) -> bool {
+        self.density(total_entities) > threshold
+    }
+}
+
+/// A read-only version of [`ComponentSparseSet`], created with [`ComponentSparseSet::freeze`].
+///
+/// Its entities and sparse index are stored in boxed slices, and it has no methods that can
+/// change its contents, so it can be shared between threads and read without synchronization.
+#[derive(Debug)]
+pub struct FrozenComponentSparseSet {
+    dense: Column,
+    #[cfg(not(debug_assertions))]
+    entities: Box<[EntityIndex]>,
+    #[cfg(debug_assertions)]
+    entities: Box<[Entity]>,
+    sparse: ImmutableSparseArray<EntityIndex, TableRow>,
+}
+
+// SAFETY: the frozen set owns its component values and their ticks outright, and components
+// are `Send` (data that isn't is only ever stored as a non-send resource), so moving the set to
+// another thread moves everything it refers to.
+unsafe impl Send for FrozenComponentSparseSet {}
+// SAFETY: through `&self`, the frozen set only hands out `Ptr`s to component values, which are
+// `Sync`. None of its methods expose the `UnsafeCell`s holding the ticks, so no thread can write
+// them while another reads them. Methods returning tick cells must not be added.
+unsafe impl Sync for FrozenComponentSparseSet {}
+
+impl FrozenComponentSparseSet {
+    /// Returns the number of component values in the sparse set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    /// Returns `true` if the sparse set contains no component values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.dense.len() == 0
+    }
+
+    /// Returns `true` if the sparse set has a component value for the provided `entity`.
+    #[inline]
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    /// Returns a reference to the entity's component value.
+    ///
+    /// Returns `None` if `entity` does not have a component in the sparse set.
+    #[inline]
+    pub fn get(&self, entity: Entity) -> Option<Ptr<'_>> {
+        self.sparse.get(entity.index()).map(|&dense_index| {
+            #[cfg(debug_assertions)]
+            assert_eq!(entity, self.entities[dense_index.as_usize()]);
+            // SAFETY: if the sparse index points to something in the dense vec, it exists
+            unsafe { self.dense.get_data_unchecked(dense_index) }
+        })
+    }
+
+    /// Iterates over the [index](Entity::index) of each entity with a component value, together
+    /// with that value.
+    ///
+    /// See [`ComponentSparseSet::iter_entities`] for why only indices are returned.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, Ptr<'_>)> {
+        self.entities
+            .iter()
+            .enumerate()
+            .map(|(dense_index, entity)| {
+                #[cfg(not(debug_assertions))]
+                let index = *entity;
+                #[cfg(debug_assertions)]
+                let index = entity.index();
+                // SAFETY: every entity has a value at the same position in the dense column
+                let value = unsafe {
+                    self.dense
+                        .get_data_unchecked(TableRow::from_usize(dense_index))
+                };
+                (index, value)
+            })
+    }
+}
+
+/// Returns the row at which a [`ComponentSparseSet`] holding `len` values stores the next one.
+///
+/// # Panics
+/// Panics if the row does not fit in a [`TableRow`], rather than silently truncating it.
+#[inline]
+fn next_table_row(len: usize) -> TableRow {
+    assert!(
+        len < u32::MAX as usize,
+        "sparse set exceeded u32::MAX entries"
+    );
+    TableRow::from_usize(len)
+}
+
+/// Returns the dense index at which a [`SparseSet`] holding `len` values stores the next one.
+///
+/// # Panics
+/// Panics if `len` is [`usize::MAX`], which a [`NonMaxUsize`] cannot represent.
+#[inline]
+fn next_dense_index(len: usize) -> NonMaxUsize {
+    NonMaxUsize::new(len).expect("sparse set exceeded usize::MAX - 1 entries")
 }
 
 /// A data structure that blends dense and sparse storage
 ///
 /// `I` is the type of the indices, while `V` is the type of data stored in the dense storage.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SparseSet<I, V: 'static> {
     dense: Vec<V>,
     indices: Vec<I>,
     sparse: SparseArray<I, NonMaxUsize>,
+    // The largest index that may be inserted, if the set was created with `SparseSet::fixed`.
+    max_index: Option<usize>,
+    // Indices marked by `mark_for_removal`, to be removed by the next `sweep`.
+    pending_removals: Vec<I>,
+    // The number of removals since the set was last shrunk, if `auto_shrink` is enabled.
+    removals_since_shrink: Option<usize>,
 }
 
 /// A space-optimized version of [`SparseSet`] that cannot be changed
 /// after construction.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct ImmutableSparseSet<I, V: 'static> {
     dense: Box<[V]>,
     indices: Box<[I]>,
@@ -421,24 +1040,105 @@ impl<I: SparseSetIndex, V> Default for SparseSet<I, V> {
     }
 }
 
-impl<I, V> SparseSet<I, V> {
-    /// Creates a new [`SparseSet`].
-    pub const fn new() -> Self {
-        Self {
-            dense: Vec::new(),
-            indices: Vec::new(),
-            sparse: SparseArray::new(),
-        }
+/// Sets are equal if they map the same indices to equal values, whatever order they are stored in.
+impl<I: SparseSetIndex, V: PartialEq> PartialEq for SparseSet<I, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(index, value)| other.get(index.clone()) == Some(value))
     }
 }
 
-impl<I: SparseSetIndex, V> SparseSet<I, V> {
-    /// Creates a new [`SparseSet`] with a specified initial capacity.
-    pub fn with_capacity(capacity: usize) -> Self {
+impl<I: SparseSetIndex, V: Eq> Eq for SparseSet<I, V> {}
+
+impl<I: SparseSetIndex, V> Index<I> for SparseSet<I, V> {
+    type Output = V;
+
+    /// Returns a reference to the value at `index`.
+    ///
+    /// # Panics
+    /// Panics if the set contains no value at `index`.
+    #[inline]
+    fn index(&self, index: I) -> &V {
+        let sparse_index = index.sparse_set_index();
+        self.get(index)
+            .unwrap_or_else(|| panic!("no value at sparse set index {sparse_index}"))
+    }
+}
+
+impl<I: SparseSetIndex, V> IndexMut<I> for SparseSet<I, V> {
+    /// Returns a mutable reference to the value at `index`.
+    ///
+    /// # Panics
+    /// Panics if the set contains no value at `index`.
+    #[inline]
+    fn index_mut(&mut self, index: I) -> &mut V {
+        let sparse_index = index.sparse_set_index();
+        self.get_mut(index)
+            .unwrap_or_else(|| panic!("no value at sparse set index {sparse_index}"))
+    }
+}
+
+impl<I: SparseSetIndex, V> FromIterator<(I, V)> for SparseSet<I, V> {
+    fn from_iter<T: IntoIterator<Item = (I, V)>>(iter: T) -> Self {
+        let iter = iter.into_iter();
+        let mut set = Self::with_capacity(iter.size_hint().0);
+        set.extend(iter);
+        set
+    }
+}
+
+impl<I: SparseSetIndex, V> Extend<(I, V)> for SparseSet<I, V> {
+    /// Inserts each pair in turn, so a value overwrites any earlier one with the same index.
+    fn extend<T: IntoIterator<Item = (I, V)>>(&mut self, iter: T) {
+        for (index, value) in iter {
+            self.insert(index, value);
+        }
+    }
+}
+
+impl<I, V> SparseSet<I, V> {
+    /// Creates a new [`SparseSet`].
+    pub const fn new() -> Self {
+        Self {
+            dense: Vec::new(),
+            indices: Vec::new(),
+            sparse: SparseArray::new(),
+            max_index: None,
+            pending_removals: Vec::new(),
+            removals_since_shrink: None,
+        }
+    }
+}
+
+impl<I: SparseSetIndex, V> SparseSet<I, V> {
+    /// Creates a new [`SparseSet`] with a specified initial capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
             dense: Vec::with_capacity(capacity),
             indices: Vec::with_capacity(capacity),
             sparse: Default::default(),
+            max_index: None,
+            pending_removals: Vec::new(),
+            removals_since_shrink: None,
+        }
+    }
+
+    /// Creates a new [`SparseSet`] for the fixed index space `0..=max_index`.
+    ///
+    /// The sparse backing is allocated up front to cover every index in the range, and
+    /// inserting an index beyond `max_index` panics instead of growing it.
+    pub fn fixed(max_index: I) -> Self {
+        let mut sparse = SparseArray::new();
+        sparse.grow_to_include(max_index.clone());
+        Self {
+            dense: Vec::new(),
+            indices: Vec::new(),
+            sparse,
+            max_index: Some(max_index.sparse_set_index()),
+            pending_removals: Vec::new(),
+            removals_since_shrink: None,
         }
     }
 
@@ -448,6 +1148,41 @@ impl<I: SparseSetIndex, V> SparseSet<I, V> {
         self.dense.capacity()
     }
 
+    /// Reserves capacity for at least `additional` more values.
+    ///
+    /// Only the dense storage is reserved. The sparse backing is sized by the values of the
+    /// indices rather than their number, so see [`reserve_sparse_density`](Self::reserve_sparse_density)
+    /// to reserve it as well.
+    pub fn reserve(&mut self, additional: usize) {
+        self.dense.reserve(additional);
+        self.indices.reserve(additional);
+    }
+
+    /// Reserves capacity for exactly `additional` more values, without over-allocating.
+    ///
+    /// As with [`reserve`](Self::reserve), only the dense storage is reserved.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.dense.reserve_exact(additional);
+        self.indices.reserve_exact(additional);
+    }
+
+    /// Tries to reserve capacity for at least `additional` more values, returning an error
+    /// instead of aborting if the allocation fails.
+    ///
+    /// As with [`reserve`](Self::reserve), only the dense storage is reserved.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.dense.try_reserve(additional)?;
+        self.indices.try_reserve(additional)
+    }
+
+    /// Reserves room for at least `count` more values with indices up to `max_index`, so that
+    /// inserting them does not reallocate.
+    pub fn reserve_sparse_density(&mut self, count: usize, max_index: I) {
+        self.dense.reserve(count);
+        self.indices.reserve(count);
+        self.sparse.grow_to_include(max_index);
+    }
+
     /// Inserts `value` at `index`.
     ///
     /// If a value was already present at `index`, it will be overwritten.
@@ -458,13 +1193,192 @@ impl<I: SparseSetIndex, V> SparseSet<I, V> {
                 *self.dense.get_unchecked_mut(dense_index.get()) = value;
             }
         } else {
+            self.check_max_index(&index);
             self.sparse
-                .insert(index.clone(), NonMaxUsize::new(self.dense.len()).unwrap());
+                .insert(index.clone(), next_dense_index(self.dense.len()));
             self.indices.push(index);
             self.dense.push(value);
         }
     }
 
+    /// Replaces the value at `index` with `value`, returning the value it replaced along with a
+    /// reference to the newly stored value.
+    ///
+    /// If `index` has no value, nothing is inserted and `None` is returned.
+    pub fn replace(&mut self, index: I, value: V) -> Option<ReplacedEntry<'_, V>> {
+        let dense_index = self.sparse.get(index)?.get();
+        let new = &mut self.dense[dense_index];
+        let old = std::mem::replace(new, value);
+        Some(ReplacedEntry { old, new })
+    }
+
+    /// Inserts `value` at `index`, placing it directly after the value at `near` in iteration order.
+    ///
+    /// This keeps related values adjacent in memory. Later elements are shifted back by one
+    /// place, so this is `O(n)` rather than `O(1)` like [`insert`](Self::insert).
+    /// If a value was already present at `index`, it is overwritten in place, and if `near` has
+    /// no value, the new value is appended as with [`insert`](Self::insert).
+    pub fn insert_near(&mut self, index: I, value: V, near: I) {
+        if self.contains(index.clone()) {
+            self.insert(index, value);
+            return;
+        }
+        let Some(near_index) = self.sparse.get(near).map(NonMaxUsize::get) else {
+            self.insert(index, value);
+            return;
+        };
+        self.check_max_index(&index);
+        let dense_index = near_index + 1;
+        self.indices.insert(dense_index, index);
+        self.dense.insert(dense_index, value);
+        for (dense_index, index) in self.indices.iter().enumerate().skip(dense_index) {
+            self.sparse
+                .insert(index.clone(), next_dense_index(dense_index));
+        }
+    }
+
+    /// Gets the [`Entry`] for `index`, for in-place manipulation of its value.
+    pub fn entry(&mut self, index: I) -> Entry<'_, I, V> {
+        match self.sparse.get(index.clone()) {
+            Some(dense_index) => Entry::Occupied(OccupiedEntry {
+                dense_index: dense_index.get(),
+                set: self,
+            }),
+            None => Entry::Vacant(VacantEntry { set: self, index }),
+        }
+    }
+
+    /// Returns all the values as a contiguous slice.
+    ///
+    /// The slice is in the internal dense order, which is not the order of the indices and changes
+    /// as elements are removed. It is intended for bulk operations that don't depend on order.
+    #[inline]
+    pub fn values_slice(&self) -> &[V] {
+        &self.dense
+    }
+
+    /// Returns all the values as a contiguous mutable slice.
+    ///
+    /// The slice is in the internal dense order, which is not the order of the indices and changes
+    /// as elements are removed. It is intended for bulk operations that don't depend on order.
+    #[inline]
+    pub fn values_slice_mut(&mut self) -> &mut [V] {
+        &mut self.dense
+    }
+
+    /// Returns an iterator visiting all key-value pairs in arbitrary order, with mutable references
+    /// to the values and copies of the keys.
+    pub fn iter_copied_mut(&mut self) -> impl Iterator<Item = (I, &mut V)>
+    where
+        I: Copy,
+    {
+        self.indices.iter().copied().zip(self.dense.iter_mut())
+    }
+
+    /// Returns an iterator visiting all key-value pairs in ascending order of
+    /// [`sparse_set_index`](SparseSetIndex::sparse_set_index).
+    ///
+    /// Unlike [`iter`](Self::iter), the order doesn't depend on the history of insertions and
+    /// removals. This allocates a temporary permutation of the dense indices, so prefer `iter` when
+    /// the order doesn't matter.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (&I, &V)> {
+        let mut order: Vec<usize> = (0..self.dense.len()).collect();
+        order.sort_unstable_by_key(|&dense_index| self.indices[dense_index].sparse_set_index());
+        order
+            .into_iter()
+            .map(|dense_index| (&self.indices[dense_index], &self.dense[dense_index]))
+    }
+
+    /// Returns an iterator over the indices that have a value in both `self` and `other`, in
+    /// arbitrary order.
+    ///
+    /// Only the indices are compared, so the sets may hold different types of values. This visits
+    /// the smaller of the two sets.
+    pub fn intersection_keys<'a, W>(
+        &'a self,
+        other: &'a SparseSet<I, W>,
+    ) -> impl Iterator<Item = I> + 'a {
+        let (indices, sparse) = if self.len() <= other.len() {
+            (&self.indices, &other.sparse)
+        } else {
+            (&other.indices, &self.sparse)
+        };
+        indices
+            .iter()
+            .filter(|index| sparse.contains((*index).clone()))
+            .cloned()
+    }
+
+    /// Returns an iterator over the indices that have a value in `self` but not in `other`, in
+    /// arbitrary order.
+    ///
+    /// Only the indices are compared, so the sets may hold different types of values.
+    pub fn difference_keys<'a, W>(
+        &'a self,
+        other: &'a SparseSet<I, W>,
+    ) -> impl Iterator<Item = I> + 'a {
+        self.indices
+            .iter()
+            .filter(|index| !other.contains((*index).clone()))
+            .cloned()
+    }
+
+    /// Swaps the positions of the values for `a` and `b`, which changes the order in which
+    /// [`iter`](Self::iter) and [`values`](Self::values) visit them.
+    ///
+    /// # Panics
+    /// Panics if `a` or `b` does not have a value in the sparse set, like indexing a [`Vec`] out of
+    /// bounds, so that a stale index is not mistaken for a successful reorder.
+    pub fn swap(&mut self, a: I, b: I) {
+        let [dense_a, dense_b] = [a, b].map(|index| {
+            let sparse_index = index.sparse_set_index();
+            *self
+                .sparse
+                .get(index)
+                .unwrap_or_else(|| panic!("no value at sparse set index {sparse_index}"))
+        });
+        self.dense.swap(dense_a.get(), dense_b.get());
+        self.indices.swap(dense_a.get(), dense_b.get());
+        *self
+            .sparse
+            .get_mut(self.indices[dense_a.get()].clone())
+            .unwrap() = dense_a;
+        *self
+            .sparse
+            .get_mut(self.indices[dense_b.get()].clone())
+            .unwrap() = dense_b;
+    }
+
+    /// Returns a reference to the value for each of `indices`, in the same order.
+    ///
+    /// Each element is `None` if its index does not have a value. Indices may be repeated, in which
+    /// case they refer to the same value.
+    pub fn get_many<const N: usize>(&self, indices: [I; N]) -> [Option<&V>; N] {
+        indices.map(|index| self.get(index))
+    }
+
+    /// Returns a mutable reference to the value for each of `indices`, in the same order.
+    ///
+    /// Each element is `None` if its index does not have a value.
+    ///
+    /// # Panics
+    /// Panics if the same index with a value is requested more than once.
+    pub fn get_disjoint_mut<const N: usize>(&mut self, indices: [I; N]) -> [Option<&mut V>; N] {
+        let dense_indices = indices.map(|index| self.sparse.get(index).map(NonMaxUsize::get));
+        for (i, dense_index) in dense_indices.iter().enumerate() {
+            assert!(
+                dense_index.is_none() || !dense_indices[..i].contains(dense_index),
+                "get_disjoint_mut requested the same index more than once"
+            );
+        }
+        let dense = self.dense.as_mut_ptr();
+        dense_indices.map(|dense_index| {
+            // SAFETY: every dense index comes from the lookup, so it is in bounds, and they were
+            // checked to be distinct above, so the references don't alias.
+            dense_index.map(|dense_index| unsafe { &mut *dense.add(dense_index) })
+        })
+    }
+
     /// Returns a reference to the value for `index`, inserting one computed from `func`
     /// if not already present.
     pub fn get_or_insert_with(&mut self, index: I, func: impl FnOnce() -> V) -> &mut V {
@@ -472,10 +1386,11 @@ impl<I: SparseSetIndex, V> SparseSet<I, V> {
             // SAFETY: dense indices stored in self.sparse always exist
             unsafe { self.dense.get_unchecked_mut(dense_index.get()) }
         } else {
+            self.check_max_index(&index);
             let value = func();
             let dense_index = self.dense.len();
             self.sparse
-                .insert(index.clone(), NonMaxUsize::new(dense_index).unwrap());
+                .insert(index.clone(), next_dense_index(dense_index));
             self.indices.push(index);
             self.dense.push(value);
             // SAFETY: dense index was just populated above
@@ -483,17 +1398,75 @@ impl<I: SparseSetIndex, V> SparseSet<I, V> {
         }
     }
 
+    /// Returns a mutable reference to the value for `index`, inserting the default value first
+    /// if not already present.
+    pub fn get_or_insert_default(&mut self, index: I) -> &mut V
+    where
+        V: Default,
+    {
+        self.get_or_insert_with(index, V::default)
+    }
+
+    /// Returns the stored key for `index` along with a mutable reference to its value.
+    ///
+    /// The key is only borrowed immutably, as changing it would desynchronize the sparse lookup.
+    ///
+    /// Returns `None` if `index` does not have a value in the sparse set.
+    pub fn get_entry_mut(&mut self, index: I) -> Option<(&I, &mut V)> {
+        let dense_index = self.sparse.get(index)?.get();
+        Some((&self.indices[dense_index], &mut self.dense[dense_index]))
+    }
+
+    /// Panics if `index` is beyond the bounds of a set created with [`SparseSet::fixed`].
+    #[inline]
+    fn check_max_index(&self, index: &I) {
+        if let Some(max_index) = self.max_index {
+            let index = index.sparse_set_index();
+            assert!(
+                index <= max_index,
+                "index {index} is out of bounds for a fixed SparseSet with max index {max_index}"
+            );
+        }
+    }
+
     /// Returns `true` if the sparse set contains no elements.
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.dense.len() == 0
     }
 
+    /// Returns the number of slots in the sparse backing, including the ones without a value.
+    ///
+    /// The sparse backing grows with the largest index stored rather than with [`len`](Self::len),
+    /// so a large ratio between the two means the indices in use are sparse. See
+    /// [`SparseSetIndex`].
+    pub fn sparse_len(&self) -> usize {
+        self.sparse.len()
+    }
+
+    /// Returns an iterator over contiguous slices of at most `chunk_size` values, in the
+    /// same order as [`values`](Self::values).
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is zero.
+    pub fn chunks(&self, chunk_size: usize) -> impl Iterator<Item = &[V]> {
+        self.dense.chunks(chunk_size)
+    }
+
+    /// Returns an iterator over contiguous slices of at most `chunk_size` keys, lining up
+    /// with the slices returned by [`chunks`](Self::chunks).
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is zero.
+    pub fn key_chunks(&self, chunk_size: usize) -> impl Iterator<Item = &[I]> {
+        self.indices.chunks(chunk_size)
+    }
+
     /// Removes and returns the value for `index`.
     ///
     /// Returns `None` if `index` does not have a value in the sparse set.
     pub fn remove(&mut self, index: I) -> Option<V> {
-        self.sparse.remove(index).map(|dense_index| {
+        let value = self.sparse.remove(index).map(|dense_index| {
             let index = dense_index.get();
             let is_last = index == self.dense.len() - 1;
             let value = self.dense.swap_remove(index);
@@ -503,7 +1476,202 @@ impl<I: SparseSetIndex, V> SparseSet<I, V> {
                 *self.sparse.get_mut(swapped_index).unwrap() = dense_index;
             }
             value
-        })
+        })?;
+        self.record_removals(1);
+        Some(value)
+    }
+
+    /// Removes and returns the value for `index`, moving the elements after it back by one place
+    /// so that the order of the others is preserved.
+    ///
+    /// This takes O(n) time, as every element after the removed one must be moved and have its
+    /// sparse index updated. Prefer [`remove`](Self::remove), which takes O(1) time, when the
+    /// order doesn't matter.
+    ///
+    /// Returns `None` if `index` does not have a value in the sparse set.
+    pub fn shift_remove(&mut self, index: I) -> Option<V> {
+        let dense_index = self.sparse.remove(index)?.get();
+        self.indices.remove(dense_index);
+        let value = self.dense.remove(dense_index);
+        for (dense_index, index) in self.indices.iter().enumerate().skip(dense_index) {
+            *self.sparse.get_mut(index.clone()).unwrap() = next_dense_index(dense_index);
+        }
+        self.record_removals(1);
+        Some(value)
+    }
+
+    /// Enables or disables shrinking the set automatically as elements are removed.
+    ///
+    /// When enabled, the dense storage is shrunk to fit once more elements have been removed since
+    /// it was last shrunk than half of its capacity. Tying the threshold to the capacity keeps the
+    /// cost of shrinking amortized over the removals that led to it.
+    ///
+    /// The sparse backing is left alone, as trimming it scans every slot up to the largest index,
+    /// which can be far more than the number of elements. Use
+    /// [`shrink_sparse_to_fit`](Self::shrink_sparse_to_fit) to trim it as well.
+    pub fn auto_shrink(&mut self, enabled: bool) {
+        self.removals_since_shrink = enabled.then_some(0);
+    }
+
+    /// Counts `removed` elements towards the next automatic shrink, shrinking the set if it is due.
+    fn record_removals(&mut self, removed: usize) {
+        let Some(removals) = &mut self.removals_since_shrink else {
+            return;
+        };
+        *removals += removed;
+        if *removals > self.dense.capacity() / 2 {
+            *removals = 0;
+            self.dense.shrink_to_fit();
+            self.indices.shrink_to_fit();
+        }
+    }
+
+    /// Retains only the elements for which `f` returns `true`, passing each a mutable reference
+    /// to its value.
+    ///
+    /// Removed elements are swap-removed, so the order of the remaining elements may change.
+    pub fn retain(&mut self, mut f: impl FnMut(&I, &mut V) -> bool) {
+        let len = self.dense.len();
+        let mut dense_index = 0;
+        while dense_index < self.dense.len() {
+            if f(&self.indices[dense_index], &mut self.dense[dense_index]) {
+                dense_index += 1;
+            } else {
+                // The last element is swapped into `dense_index`, so it is visited next.
+                self.swap_remove_dense(dense_index);
+            }
+        }
+        self.record_removals(len - self.dense.len());
+    }
+
+    /// Retains only the elements for which `f` returns `true`, and returns the number of
+    /// elements that were removed.
+    ///
+    /// Removed elements are swap-removed, so the order of the remaining elements may change.
+    pub fn retain_counted<F: FnMut(&I, &V) -> bool>(&mut self, mut f: F) -> usize {
+        let len = self.dense.len();
+        self.retain(|index, value| f(index, value));
+        len - self.dense.len()
+    }
+
+    /// Runs `func` on every value in parallel, in batches of up to `batch_size` values.
+    ///
+    /// # Panics
+    /// If `batch_size` is zero, or if the [`ComputeTaskPool`] is not initialized.
+    ///
+    /// [`ComputeTaskPool`]: bevy_tasks::ComputeTaskPool
+    #[cfg(feature = "multi_threaded")]
+    pub fn par_values_mut(&mut self, batch_size: usize, func: impl Fn(&mut V) + Sync)
+    where
+        V: Send,
+    {
+        assert!(batch_size > 0, "batch_size must be non-zero");
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.dense.iter_mut().for_each(func);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let func = &func;
+            bevy_tasks::ComputeTaskPool::get().scope(|scope| {
+                for batch in self.dense.chunks_mut(batch_size) {
+                    scope.spawn(async move { batch.iter_mut().for_each(func) });
+                }
+            });
+        }
+    }
+
+    /// Marks `index` to be removed by the next call to [`sweep`](Self::sweep).
+    ///
+    /// This allows removals to be decided while iterating over the set, and carried out afterwards.
+    pub fn mark_for_removal(&mut self, index: I) {
+        self.pending_removals.push(index);
+    }
+
+    /// Removes every element marked with [`mark_for_removal`](Self::mark_for_removal), returning
+    /// the number of elements that were removed.
+    ///
+    /// Marked indices that have no value by the time of the sweep are ignored.
+    pub fn sweep(&mut self) -> usize {
+        let mut pending_removals = std::mem::take(&mut self.pending_removals);
+        let mut removed = 0;
+        for index in pending_removals.drain(..) {
+            if let Some(dense_index) = self.sparse.get(index) {
+                self.swap_remove_dense(dense_index.get());
+                removed += 1;
+            }
+        }
+        // Keep the allocation for the next round of marking.
+        self.pending_removals = pending_removals;
+        self.record_removals(removed);
+        removed
+    }
+
+    /// Removes every element whose index lies within `range`, returning the number removed.
+    ///
+    /// Only the slots of the sparse array within `range` are visited, rather than every element.
+    /// Removed elements are swap-removed, so the order of the remaining elements may change.
+    pub fn clear_range(&mut self, range: impl RangeBounds<I>) -> usize {
+        let start = match range.start_bound() {
+            Bound::Included(index) => index.sparse_set_index(),
+            Bound::Excluded(index) => index.sparse_set_index().saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(index) => index.sparse_set_index().saturating_add(1),
+            Bound::Excluded(index) => index.sparse_set_index(),
+            Bound::Unbounded => usize::MAX,
+        }
+        .min(self.sparse.pages.len() * SPARSE_ARRAY_PAGE_LEN);
+        let mut removed = 0;
+        let mut slot = start;
+        while slot < end {
+            let (page, offset) = page_and_offset::<SPARSE_ARRAY_PAGE_LEN>(slot);
+            match self.sparse.pages[page].get(offset) {
+                Some(&Some(dense_index)) => {
+                    self.swap_remove_dense(dense_index.get());
+                    removed += 1;
+                }
+                Some(None) => {}
+                // The rest of the page holds no values.
+                None => {
+                    slot = (page + 1) * SPARSE_ARRAY_PAGE_LEN;
+                    continue;
+                }
+            }
+            slot += 1;
+        }
+        self.record_removals(removed);
+        removed
+    }
+
+    /// Rotates the elements `n` places to the left in iteration order, so that iteration starts
+    /// from the element that was previously `n`-th.
+    ///
+    /// The key-value pairs themselves are unchanged.
+    pub fn rotate_dense(&mut self, n: usize) {
+        if self.dense.is_empty() {
+            return;
+        }
+        let n = n % self.dense.len();
+        self.dense.rotate_left(n);
+        self.indices.rotate_left(n);
+        for (dense_index, index) in self.indices.iter().enumerate() {
+            *self.sparse.get_mut(index.clone()).unwrap() = next_dense_index(dense_index);
+        }
+    }
+
+    /// Swap-removes the element at `dense_index`, returning its key and value.
+    fn swap_remove_dense(&mut self, dense_index: usize) -> (I, V) {
+        let index = self.indices.swap_remove(dense_index);
+        let value = self.dense.swap_remove(dense_index);
+        self.sparse.remove(index.clone());
+        if dense_index < self.dense.len() {
+            let swapped_index = self.indices[dense_index].clone();
+            *self.sparse.get_mut(swapped_index).unwrap() = next_dense_index(dense_index);
+        }
+        (index, value)
     }
 
     /// Clears all of the elements from the sparse set.
@@ -511,6 +1679,125 @@ impl<I: SparseSetIndex, V> SparseSet<I, V> {
         self.dense.clear();
         self.indices.clear();
         self.sparse.clear();
+        self.pending_removals.clear();
+    }
+
+    /// Removes all of the elements from the sparse set, returning them as an iterator of
+    /// `(index, value)` pairs.
+    ///
+    /// The sparse set is left empty even if the iterator is dropped before it is fully consumed,
+    /// and keeps its allocations for reuse.
+    pub fn drain(&mut self) -> impl Iterator<Item = (I, V)> + '_ {
+        // Clear the lookup up front so the set is consistent however the iterator is dropped.
+        self.sparse.clear();
+        self.pending_removals.clear();
+        self.indices.drain(..).zip(self.dense.drain(..))
+    }
+
+    /// Shrinks the sparse backing to fit the highest index currently stored.
+    ///
+    /// The sparse backing is sized by the largest index ever inserted, so it is often the
+    /// largest allocation of a sparse set. Unlike a full shrink, the dense storage is left alone.
+    pub fn shrink_sparse_to_fit(&mut self) {
+        self.sparse.shrink();
+    }
+
+    /// Shrinks the dense storage to fit the number of elements, and the sparse backing to fit
+    /// the highest index currently stored.
+    pub fn shrink_to_fit(&mut self) {
+        self.dense.shrink_to_fit();
+        self.indices.shrink_to_fit();
+        self.sparse.shrink();
+        if let Some(removals) = &mut self.removals_since_shrink {
+            *removals = 0;
+        }
+    }
+
+    /// Decomposes the sparse set into its indices, its values, and the sparse array mapping each
+    /// index to its position in the other two.
+    ///
+    /// The parts can be reassembled with [`from_parts`](Self::from_parts). Any bound set by
+    /// [`fixed`](Self::fixed) is not preserved.
+    pub fn into_parts(self) -> (Vec<I>, Vec<V>, SparseArray<I, NonMaxUsize>) {
+        (self.indices, self.dense, self.sparse)
+    }
+
+    /// Consumes the sparse set, returning one with the same values in the same order, but with
+    /// each index replaced by the result of passing it to `f`.
+    ///
+    /// # Panics
+    /// `f` must map distinct indices to distinct indices, and panics if two indices are mapped to
+    /// the same one.
+    pub fn map_keys<J: SparseSetIndex>(self, f: impl Fn(I) -> J) -> SparseSet<J, V> {
+        let mut sparse = SparseArray::new();
+        let indices = self
+            .indices
+            .into_iter()
+            .enumerate()
+            .map(|(dense_index, index)| {
+                let index = f(index);
+                assert!(
+                    !sparse.contains(index.clone()),
+                    "map_keys mapped two indices to index {}",
+                    index.sparse_set_index()
+                );
+                sparse.insert(index.clone(), next_dense_index(dense_index));
+                index
+            })
+            .collect();
+        SparseSet {
+            dense: self.dense,
+            indices,
+            sparse,
+            max_index: None,
+            pending_removals: Vec::new(),
+            removals_since_shrink: None,
+        }
+    }
+
+    /// Consumes the sparse set, returning its values and, at the same positions, their indices.
+    ///
+    /// Unlike [`into_parts`](Self::into_parts), the sparse array is dropped, so lookups by index
+    /// have to go through the returned index vector.
+    pub fn into_dense_with_keys(self) -> (Vec<V>, Vec<I>) {
+        (self.dense, self.indices)
+    }
+
+    /// Assembles a sparse set from parts produced by [`into_parts`](Self::into_parts).
+    ///
+    /// `indices[i]` must be the index of `dense[i]`, and `sparse` must map each of those indices
+    /// back to `i` and contain no other entries. Returns a [`PartsError`] if it does not.
+    pub fn from_parts(
+        indices: Vec<I>,
+        dense: Vec<V>,
+        sparse: SparseArray<I, NonMaxUsize>,
+    ) -> Result<Self, PartsError> {
+        if indices.len() != dense.len() {
+            return Err(PartsError::LengthMismatch {
+                indices: indices.len(),
+                dense: dense.len(),
+            });
+        }
+        for (dense_index, index) in indices.iter().enumerate() {
+            if sparse.get(index.clone()).map(NonMaxUsize::get) != Some(dense_index) {
+                return Err(PartsError::UnmappedIndex(dense_index));
+            }
+        }
+        let mapped = sparse.iter().count();
+        if mapped != indices.len() {
+            return Err(PartsError::ExtraSparseEntries {
+                mapped,
+                dense: dense.len(),
+            });
+        }
+        Ok(Self {
+            dense,
+            indices,
+            sparse,
+            max_index: None,
+            pending_removals: Vec::new(),
+            removals_since_shrink: None,
+        })
     }
 
     /// Converts the sparse set into its immutable variant.
@@ -523,67 +1810,432 @@ impl<I: SparseSetIndex, V> SparseSet<I, V> {
     }
 }
 
-/// Represents something that can be stored in a [`SparseSet`] as an integer.
-///
-/// Ideally, the `usize` values should be very small (ie: incremented starting from
-/// zero), as the number of bits needed to represent a `SparseSetIndex` in a `FixedBitSet`
-/// is proportional to the **value** of those `usize`.
-pub trait SparseSetIndex: Clone + PartialEq + Eq + Hash {
-    /// Gets the sparse set index corresponding to this instance.
-    fn sparse_set_index(&self) -> usize;
-    /// Creates a new instance of this type with the specified index.
-    fn get_sparse_set_index(value: usize) -> Self;
+/// Serializes the set as a sequence of `(index, value)` pairs in dense order.
+#[cfg(feature = "serialize")]
+impl<I: SparseSetIndex + Serialize, V: Serialize> Serialize for SparseSet<I, V> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.iter())
+    }
 }
 
-macro_rules! impl_sparse_set_index {
-    ($($ty:ty),+) => {
-        $(impl SparseSetIndex for $ty {
-            #[inline]
-            fn sparse_set_index(&self) -> usize {
-                *self as usize
+/// Deserializes a sequence of `(index, value)` pairs, failing if an index appears more than once.
+#[cfg(feature = "serialize")]
+impl<'de, I, V> Deserialize<'de> for SparseSet<I, V>
+where
+    I: SparseSetIndex + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct SparseSetVisitor<I, V>(PhantomData<(I, V)>);
+
+        impl<'de, I, V> serde::de::Visitor<'de> for SparseSetVisitor<I, V>
+        where
+            I: SparseSetIndex + Deserialize<'de>,
+            V: Deserialize<'de> + 'static,
+        {
+            type Value = SparseSet<I, V>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a sequence of (index, value) pairs")
             }
 
-            #[inline]
-            fn get_sparse_set_index(value: usize) -> Self {
-                value as $ty
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                use serde::de::Error;
+                let mut set = SparseSet::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some((index, value)) = seq.next_element::<(I, V)>()? {
+                    if set.contains(index.clone()) {
+                        return Err(A::Error::custom(format_args!(
+                            "duplicate index {}",
+                            index.sparse_set_index()
+                        )));
+                    }
+                    set.insert(index, value);
+                }
+                Ok(set)
             }
-        })*
-    };
-}
+        }
 
-impl_sparse_set_index!(u8, u16, u32, u64, usize);
+        deserializer.deserialize_seq(SparseSetVisitor(PhantomData))
+    }
+}
 
-/// A collection of [`ComponentSparseSet`] storages, indexed by [`ComponentId`]
+/// A view into a single index of a [`SparseSet`], which may either be vacant or occupied.
 ///
-/// Can be accessed via [`Storages`](crate::storage::Storages)
-#[derive(Default)]
-pub struct SparseSets {
-    sets: SparseSet<ComponentId, ComponentSparseSet>,
+/// Returned by [`SparseSet::entry`].
+pub enum Entry<'a, I, V: 'static> {
+    /// The index has a value.
+    Occupied(OccupiedEntry<'a, I, V>),
+    /// The index has no value.
+    Vacant(VacantEntry<'a, I, V>),
 }
 
-impl SparseSets {
-    /// Returns the number of [`ComponentSparseSet`]s this collection contains.
+impl<'a, I: SparseSetIndex, V> Entry<'a, I, V> {
+    /// Returns the index of this entry.
     #[inline]
-    pub fn len(&self) -> usize {
-        self.sets.len()
+    pub fn key(&self) -> &I {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
     }
 
-    /// Returns true if this collection contains no [`ComponentSparseSet`]s.
+    /// Returns a mutable reference to the value of this entry, inserting `default` if it is vacant.
     #[inline]
-    pub fn is_empty(&self) -> bool {
-        self.sets.is_empty()
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
     }
 
-    /// An Iterator visiting all ([`ComponentId`], [`ComponentSparseSet`]) pairs.
-    /// NOTE: Order is not guaranteed.
-    pub fn iter(&self) -> impl Iterator<Item = (ComponentId, &ComponentSparseSet)> {
-        self.sets.iter().map(|(id, data)| (*id, data))
+    /// Returns a mutable reference to the value of this entry, inserting the result of `default`
+    /// if it is vacant.
+    #[inline]
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
     }
 
-    /// Gets a reference to the [`ComponentSparseSet`] of a [`ComponentId`].
+    /// Calls `f` with the value of this entry if it is occupied, then returns the entry.
     #[inline]
-    pub fn get(&self, component_id: ComponentId) -> Option<&ComponentSparseSet> {
-        self.sets.get(component_id)
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+/// A view into an index of a [`SparseSet`] that has a value. Part of the [`Entry`] enum.
+pub struct OccupiedEntry<'a, I, V: 'static> {
+    set: &'a mut SparseSet<I, V>,
+    dense_index: usize,
+}
+
+impl<'a, I: SparseSetIndex, V> OccupiedEntry<'a, I, V> {
+    /// Returns the index of this entry.
+    #[inline]
+    pub fn key(&self) -> &I {
+        &self.set.indices[self.dense_index]
+    }
+
+    /// Returns a reference to the value of this entry.
+    #[inline]
+    pub fn get(&self) -> &V {
+        &self.set.dense[self.dense_index]
+    }
+
+    /// Returns a mutable reference to the value of this entry.
+    ///
+    /// To keep the reference after the entry is dropped, use [`into_mut`](Self::into_mut).
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.set.dense[self.dense_index]
+    }
+
+    /// Converts the entry into a mutable reference to its value, bound to the lifetime of the set.
+    #[inline]
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.set.dense[self.dense_index]
+    }
+
+    /// Replaces the value of this entry with `value`, returning the old value.
+    #[inline]
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+}
+
+/// A view into an index of a [`SparseSet`] that has no value. Part of the [`Entry`] enum.
+pub struct VacantEntry<'a, I, V: 'static> {
+    set: &'a mut SparseSet<I, V>,
+    index: I,
+}
+
+impl<'a, I: SparseSetIndex, V> VacantEntry<'a, I, V> {
+    /// Returns the index of this entry.
+    #[inline]
+    pub fn key(&self) -> &I {
+        &self.index
+    }
+
+    /// Inserts `value` at the index of this entry, returning a mutable reference to it.
+    #[inline]
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.insert_entry(value).into_mut()
+    }
+
+    /// Inserts `value` at the index of this entry, returning an [`OccupiedEntry`] for it.
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, I, V> {
+        let set = self.set;
+        set.check_max_index(&self.index);
+        let dense_index = set.dense.len();
+        set.sparse
+            .insert(self.index.clone(), next_dense_index(dense_index));
+        set.indices.push(self.index);
+        set.dense.push(value);
+        OccupiedEntry { set, dense_index }
+    }
+}
+
+/// The result of replacing a value in a [`SparseSet`] with [`SparseSet::replace`].
+#[derive(Debug)]
+pub struct ReplacedEntry<'a, V> {
+    /// The value that was replaced.
+    pub old: V,
+    /// The value now stored in the sparse set.
+    pub new: &'a mut V,
+}
+
+/// An error returned by [`SparseSet::from_parts`] when its parts are not consistent with each other.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PartsError {
+    /// The index and value buffers have different lengths.
+    #[error("sparse set parts have {indices} indices but {dense} values")]
+    LengthMismatch {
+        /// The number of indices.
+        indices: usize,
+        /// The number of values.
+        dense: usize,
+    },
+    /// The sparse array does not map the index at this dense position back to it.
+    #[error("the index at dense position {0} is not mapped to that position by the sparse array")]
+    UnmappedIndex(usize),
+    /// The sparse array maps indices that have no value.
+    #[error("the sparse array maps {mapped} indices but there are only {dense} values")]
+    ExtraSparseEntries {
+        /// The number of indices mapped by the sparse array.
+        mapped: usize,
+        /// The number of values.
+        dense: usize,
+    },
+}
+
+/// A [`SparseSet`] that tracks a version for each of its values.
+///
+/// Every [`insert`](Self::insert) and [`get_mut`](Self::get_mut) stamps the affected value with
+/// a new version taken from a counter shared by the whole set, so a version only ever advances
+/// while its value may have changed. Derived caches can store the last version they saw and
+/// recompute only when [`version`](Self::version) reports a different one.
+#[derive(Debug)]
+pub struct VersionedSparseSet<I, V: 'static> {
+    set: SparseSet<I, (u32, V)>,
+    next_version: u32,
+}
+
+impl<I: SparseSetIndex, V> Default for VersionedSparseSet<I, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, V> VersionedSparseSet<I, V> {
+    /// Creates a new [`VersionedSparseSet`].
+    pub const fn new() -> Self {
+        Self {
+            set: SparseSet::new(),
+            next_version: 0,
+        }
+    }
+}
+
+impl<I: SparseSetIndex, V> VersionedSparseSet<I, V> {
+    /// Returns the number of elements in the sparse set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    /// Returns `true` if the sparse set contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// Returns `true` if the sparse set contains a value for `index`.
+    #[inline]
+    pub fn contains(&self, index: I) -> bool {
+        self.set.contains(index)
+    }
+
+    /// Returns the current version of the value for `index`.
+    ///
+    /// Returns `None` if `index` does not have a value in the sparse set.
+    #[inline]
+    pub fn version(&self, index: I) -> Option<u32> {
+        self.set.get(index).map(|(version, _)| *version)
+    }
+
+    /// Returns a reference to the value for `index`.
+    ///
+    /// Returns `None` if `index` does not have a value in the sparse set.
+    pub fn get(&self, index: I) -> Option<&V> {
+        self.set.get(index).map(|(_, value)| value)
+    }
+
+    /// Returns a mutable reference to the value for `index`, advancing its version.
+    ///
+    /// Returns `None` if `index` does not have a value in the sparse set.
+    pub fn get_mut(&mut self, index: I) -> Option<&mut V> {
+        let (version, value) = self.set.get_mut(index)?;
+        *version = self.next_version;
+        self.next_version = self.next_version.wrapping_add(1);
+        Some(value)
+    }
+
+    /// Inserts `value` at `index`, advancing its version.
+    ///
+    /// If a value was already present at `index`, it will be overwritten.
+    pub fn insert(&mut self, index: I, value: V) {
+        self.set.insert(index, (self.next_version, value));
+        self.next_version = self.next_version.wrapping_add(1);
+    }
+
+    /// Removes and returns the value for `index`.
+    ///
+    /// Returns `None` if `index` does not have a value in the sparse set.
+    pub fn remove(&mut self, index: I) -> Option<V> {
+        self.set.remove(index).map(|(_, value)| value)
+    }
+
+    /// Returns an iterator visiting all key-value pairs in arbitrary order, with references to the values.
+    pub fn iter(&self) -> impl Iterator<Item = (&I, &V)> {
+        self.set.iter().map(|(index, (_, value))| (index, value))
+    }
+
+    /// Clears all of the elements from the sparse set.
+    ///
+    /// Versions keep advancing from where they left off, so values inserted afterwards are never
+    /// mistaken for the ones that were cleared.
+    pub fn clear(&mut self) {
+        self.set.clear();
+    }
+}
+
+/// Represents something that can be stored in a [`SparseSet`] as an integer.
+///
+/// Ideally, the `usize` values should be very small (ie: incremented starting from
+/// zero), as the number of bits needed to represent a `SparseSetIndex` in a `FixedBitSet`
+/// is proportional to the **value** of those `usize`.
+pub trait SparseSetIndex: Clone + PartialEq + Eq + Hash {
+    /// Gets the sparse set index corresponding to this instance.
+    fn sparse_set_index(&self) -> usize;
+    /// Creates a new instance of this type with the specified index.
+    fn get_sparse_set_index(value: usize) -> Self;
+}
+
+macro_rules! impl_sparse_set_index {
+    ($($ty:ty),+) => {
+        $(impl SparseSetIndex for $ty {
+            #[inline]
+            fn sparse_set_index(&self) -> usize {
+                *self as usize
+            }
+
+            #[inline]
+            fn get_sparse_set_index(value: usize) -> Self {
+                value as $ty
+            }
+        })*
+    };
+}
+
+impl_sparse_set_index!(u8, u16, u32, u64, usize);
+
+impl SparseSetIndex for NonMaxU32 {
+    #[inline]
+    fn sparse_set_index(&self) -> usize {
+        self.get() as usize
+    }
+
+    /// # Panics
+    /// Panics if `value` is [`u32::MAX`] or larger, which a [`NonMaxU32`] cannot represent.
+    #[inline]
+    fn get_sparse_set_index(value: usize) -> Self {
+        u32::try_from(value)
+            .ok()
+            .and_then(NonMaxU32::new)
+            .expect("index does not fit in a NonMaxU32")
+    }
+}
+
+impl SparseSetIndex for NonMaxUsize {
+    #[inline]
+    fn sparse_set_index(&self) -> usize {
+        self.get()
+    }
+
+    /// # Panics
+    /// Panics if `value` is [`usize::MAX`], which a [`NonMaxUsize`] cannot represent.
+    #[inline]
+    fn get_sparse_set_index(value: usize) -> Self {
+        NonMaxUsize::new(value).expect("usize::MAX is not a valid NonMaxUsize index")
+    }
+}
+
+/// A collection of [`ComponentSparseSet`] storages, indexed by [`ComponentId`]
+///
+/// Can be accessed via [`Storages`](crate::storage::Storages)
+#[derive(Default)]
+pub struct SparseSets {
+    sets: SparseSet<ComponentId, ComponentSparseSet>,
+}
+
+impl SparseSets {
+    /// Returns the number of [`ComponentSparseSet`]s this collection contains.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Returns true if this collection contains no [`ComponentSparseSet`]s.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// An Iterator visiting all ([`ComponentId`], [`ComponentSparseSet`]) pairs.
+    ///
+    /// Storages are visited in the order they were created, which removing a storage does not
+    /// change. The same sequence of registrations therefore always iterates the same way.
+    pub fn iter(&self) -> impl Iterator<Item = (ComponentId, &ComponentSparseSet)> {
+        self.sets.iter().map(|(id, data)| (*id, data))
+    }
+
+    /// Returns an iterator visiting each [`ComponentId`] and mutable access to its
+    /// [`ComponentSparseSet`], in the same order as [`iter`](Self::iter).
+    pub(crate) fn iter_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (ComponentId, &mut ComponentSparseSet)> {
+        self.sets.iter_mut().map(|(id, data)| (*id, data))
+    }
+
+    /// Gets a reference to the [`ComponentSparseSet`] of a [`ComponentId`].
+    #[inline]
+    pub fn get(&self, component_id: ComponentId) -> Option<&ComponentSparseSet> {
+        self.sets.get(component_id)
+    }
+
+    /// Returns a [`SparseSetReadHandle`] for the [`ComponentSparseSet`] of each of `component_ids`,
+    /// which can be sent to other threads.
+    ///
+    /// Components without a sparse set are skipped, so the handles may be fewer than `component_ids`.
+    pub fn split_disjoint(&self, component_ids: &[ComponentId]) -> Vec<SparseSetReadHandle<'_>> {
+        component_ids
+            .iter()
+            .filter_map(|&component_id| {
+                let set = self.sets.get(component_id)?;
+                Some(SparseSetReadHandle { component_id, set })
+            })
+            .collect()
     }
 
     /// Gets a mutable reference of [`ComponentSparseSet`] of a [`ComponentInfo`].
@@ -591,15 +2243,37 @@ impl SparseSets {
     pub(crate) fn get_or_insert(
         &mut self,
         component_info: &ComponentInfo,
+    ) -> &mut ComponentSparseSet {
+        self.get_or_insert_with_capacity(component_info, 64)
+    }
+
+    /// Gets a mutable reference of [`ComponentSparseSet`] of a [`ComponentInfo`].
+    /// Create a new [`ComponentSparseSet`] with room for `capacity` values if not exists.
+    pub(crate) fn get_or_insert_with_capacity(
+        &mut self,
+        component_info: &ComponentInfo,
+        capacity: usize,
     ) -> &mut ComponentSparseSet {
         if !self.sets.contains(component_info.id()) {
             self.sets.insert(
                 component_info.id(),
-                ComponentSparseSet::new(component_info, 64),
+                ComponentSparseSet::new(component_info, capacity),
             );
         }
 
-        self.sets.get_mut(component_info.id()).unwrap()
+        let set = self.sets.get_mut(component_info.id()).unwrap();
+        debug_assert!(set.layout_matches(component_info));
+        set
+    }
+
+    /// Removes and returns the [`ComponentSparseSet`] of a [`ComponentId`], releasing the storage
+    /// once it is dropped.
+    ///
+    /// The remaining storages keep their relative order, as [`iter`](Self::iter) relies on.
+    // TODO: call this once unregistering a component frees its storage
+    #[allow(dead_code)]
+    pub(crate) fn remove(&mut self, component_id: ComponentId) -> Option<ComponentSparseSet> {
+        self.sets.shift_remove(component_id)
     }
 
     /// Gets a mutable reference to the [`ComponentSparseSet`] of a [`ComponentId`].
@@ -607,11 +2281,85 @@ impl SparseSets {
         self.sets.get_mut(component_id)
     }
 
+    /// Returns the number of bytes allocated by all the [`ComponentSparseSet`]s in this collection.
+    ///
+    /// See [`ComponentSparseSet::heap_bytes`].
+    pub fn total_heap_bytes(&self) -> usize {
+        self.sets.values().map(ComponentSparseSet::heap_bytes).sum()
+    }
+
+    /// Panics if the [`ComponentSparseSet`]s in this collection allocate more than `max_bytes`
+    /// in total, listing the bytes allocated by each of them.
+    #[cfg(test)]
+    #[track_caller]
+    pub(crate) fn assert_memory_under(&self, max_bytes: usize) {
+        let total = self.total_heap_bytes();
+        if total > max_bytes {
+            let breakdown: String = self
+                .iter()
+                .map(|(id, set)| format!("\n  {id:?}: {} bytes", set.heap_bytes()))
+                .collect();
+            panic!(
+                "sparse sets allocate {total} bytes, over the budget of {max_bytes} bytes:{breakdown}"
+            );
+        }
+    }
+
+    /// Sets the bit of `out` for the [index](Entity::index) of every entity that has a value in any
+    /// [`ComponentSparseSet`] in this collection, growing `out` as needed.
+    ///
+    /// Bits that are already set are left untouched.
+    pub fn entities_with_any_sparse(&self, out: &mut FixedBitSet) {
+        for set in self.sets.values() {
+            for index in set.iter_entities() {
+                let index = index as usize;
+                out.grow(index + 1);
+                out.insert(index);
+            }
+        }
+    }
+
+    /// Releases the memory the collection holds beyond what its [`ComponentSparseSet`]s need,
+    /// including the room its lookup reserves for the largest [`ComponentId`] it ever stored.
+    ///
+    /// The [`ComponentSparseSet`]s themselves are left untouched.
+    // TODO: call this once unregistering components removes their storages
+    #[allow(dead_code)]
+    pub(crate) fn compact_registry(&mut self) {
+        self.sets.shrink_to_fit();
+    }
+
     /// Clear entities stored in each [`ComponentSparseSet`]
     pub(crate) fn clear_entities(&mut self) {
+        self.clear_entities_where(|_| true);
+    }
+
+    /// Clear entities stored in each [`ComponentSparseSet`] whose [`ComponentId`] matches `predicate`,
+    /// leaving the other storages intact.
+    pub(crate) fn clear_entities_where(&mut self, predicate: impl Fn(ComponentId) -> bool) {
+        for (component_id, set) in self.iter_mut() {
+            if predicate(component_id) {
+                set.clear();
+            }
+        }
+    }
+
+    /// Removes the component values of `entity` from every [`ComponentSparseSet`] in this
+    /// collection, returning the number of storages that held one.
+    ///
+    /// Every storage is visited, so this takes time proportional to the number of storages.
+    /// Neither the entity's archetype nor any component hooks are updated, so the caller is
+    /// responsible for that bookkeeping.
+    // TODO: use this once despawning no longer walks the archetype's sparse set components
+    #[allow(dead_code)]
+    pub(crate) fn remove_entity(&mut self, entity: Entity) -> usize {
+        let mut removed = 0;
         for set in self.sets.values_mut() {
-            set.clear();
+            if set.remove(entity) {
+                removed += 1;
+            }
         }
+        removed
     }
 
     pub(crate) fn check_change_ticks(&mut self, change_tick: Tick) {
@@ -621,15 +2369,76 @@ impl SparseSets {
     }
 }
 
+/// Read-only access to the component values in the [`ComponentSparseSet`] of a single component,
+/// which can be sent to other threads.
+///
+/// Unlike a `&ComponentSparseSet`, the handle gives no access to the change detection ticks.
+///
+/// Created by [`SparseSets::split_disjoint`].
+#[derive(Clone, Copy)]
+pub struct SparseSetReadHandle<'a> {
+    component_id: ComponentId,
+    set: &'a ComponentSparseSet,
+}
+
+// SAFETY: the handle is `Sync` (see below), and only holds a shared reference, so sending it to
+// another thread is the same as sharing it with that thread.
+unsafe impl Send for SparseSetReadHandle<'_> {}
+// SAFETY: `ComponentSparseSet` is only `!Sync` because of the `UnsafeCell`s holding its ticks,
+// which other code may write through while this handle exists. The handle never exposes or reads
+// the ticks: it only reads the sparse lookup and hands out `Ptr`s to component values, none of
+// which can be modified through a shared reference, and components are `Sync`.
+unsafe impl Sync for SparseSetReadHandle<'_> {}
+
+impl<'a> SparseSetReadHandle<'a> {
+    /// Returns the [`ComponentId`] of the component stored in the sparse set.
+    #[inline]
+    pub fn component_id(&self) -> ComponentId {
+        self.component_id
+    }
+
+    /// Returns the number of component values in the sparse set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    /// Returns `true` if the sparse set contains no component values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// Returns `true` if the sparse set has a component value for the provided `entity`.
+    #[inline]
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.set.contains(entity)
+    }
+
+    /// Returns a reference to the entity's component value.
+    ///
+    /// Returns `None` if `entity` does not have a component in the sparse set.
+    #[inline]
+    pub fn get(&self, entity: Entity) -> Option<Ptr<'a>> {
+        self.set.get(entity)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::SparseSets;
+    use super::{
+        next_dense_index, next_table_row, CompactSparseArray, Entry, PagedSparseArray, PartsError,
+        SparseArray, SparseArrayChange, SparseSetIndex, SparseSets,
+    };
     use crate::{
         self as bevy_ecs,
-        component::{Component, ComponentDescriptor, ComponentId, ComponentInfo},
+        component::{Component, ComponentDescriptor, ComponentId, ComponentInfo, Tick},
         entity::Entity,
-        storage::SparseSet,
+        storage::{ComponentSparseSet, SparseSet, VersionedSparseSet},
     };
+    use bevy_ptr::OwningPtr;
+    use fixedbitset::FixedBitSet;
+    use nonmax::{NonMaxU32, NonMaxUsize};
 
     #[derive(Debug, Eq, PartialEq)]
     struct Foo(usize);
@@ -684,17 +2493,1780 @@ mod tests {
     }
 
     #[test]
-    fn sparse_sets() {
-        let mut sets = SparseSets::default();
+    fn compact_sparse_array() {
+        let mut compact = CompactSparseArray::<u32, u32>::new(u32::MAX);
+        let mut array = SparseArray::<u32, u32>::new();
+        for (index, value) in [(3, 30), (0, 0), (7, 70), (3, 31)] {
+            compact.insert(index, value);
+            array.insert(index, value);
+        }
+        assert_eq!(compact.remove(7), array.remove(7));
+        assert_eq!(compact.remove(7), array.remove(7));
+        assert_eq!(compact.remove(100), array.remove(100));
+        *compact.get_mut(0).unwrap() += 1;
+        *array.get_mut(0).unwrap() += 1;
 
-        #[derive(Component, Default, Debug)]
-        struct TestComponent1;
+        for index in 0..10 {
+            assert_eq!(compact.get(index), array.get(index));
+            assert_eq!(compact.contains(index), array.contains(index));
+        }
+        assert_eq!(compact.get_mut(5), None);
+    }
 
-        #[derive(Component, Default, Debug)]
-        struct TestComponent2;
+    #[test]
+    fn sparse_set_replace() {
+        let mut set = SparseSet::<u32, String>::new();
+        assert!(set.replace(3, "first".to_string()).is_none());
+        assert!(set.is_empty());
+        set.insert(3, "first".to_string());
 
-        assert_eq!(sets.len(), 0);
-        assert!(sets.is_empty());
+        let replaced = set.replace(3, "second".to_string()).unwrap();
+        assert_eq!(replaced.old, "first");
+        replaced.new.push_str(" edited");
+        assert_eq!(set.get(3).map(String::as_str), Some("second edited"));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn sparse_set_entry() {
+        let mut set = SparseSet::<u32, u32>::new();
+        set.insert(1, 10);
+
+        assert_eq!(set.entry(1).key(), &1);
+        assert_eq!(set.entry(4).key(), &4);
+
+        // Occupied
+        assert_eq!(*set.entry(1).or_insert(0), 10);
+        assert_eq!(*set.entry(1).or_insert_with(|| unreachable!()), 10);
+        assert_eq!(*set.entry(1).and_modify(|v| *v += 1).or_insert(0), 11);
+
+        // Vacant
+        let mut called = false;
+        assert_eq!(
+            *set.entry(2).and_modify(|_| called = true).or_insert(20),
+            20
+        );
+        assert!(!called);
+        assert_eq!(*set.entry(3).or_insert_with(|| 30), 30);
+        *set.entry(4).or_insert(40) += 1;
+
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.get(1), Some(&11));
+        assert_eq!(set.get(2), Some(&20));
+        assert_eq!(set.get(3), Some(&30));
+        assert_eq!(set.get(4), Some(&41));
+        assert_eq!(set.indices().collect::<Vec<_>>(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn sparse_set_vacant_insert_entry() {
+        let mut set = SparseSet::<u32, u32>::new();
+        set.insert(2, 20);
+
+        let Entry::Vacant(entry) = set.entry(5) else {
+            panic!("index 5 should be vacant");
+        };
+        assert_eq!(entry.key(), &5);
+        let mut entry = entry.insert_entry(50);
+        assert_eq!(entry.key(), &5);
+        assert_eq!(entry.get(), &50);
+        *entry.get_mut() += 1;
+        assert_eq!(entry.insert(60), 51);
+
+        assert_eq!(set.get(5), Some(&60));
+        assert_eq!(set.get(2), Some(&20));
+        assert_eq!(set.len(), 2);
+        let Entry::Occupied(entry) = set.entry(2) else {
+            panic!("index 2 should be occupied");
+        };
+        *entry.into_mut() = 21;
+        assert_eq!(set.get(2), Some(&21));
+    }
+
+    #[test]
+    fn sparse_set_insert_near() {
+        let mut set = SparseSet::<u32, char>::new();
+        for (index, value) in [(5, 'a'), (1, 'b'), (8, 'c')] {
+            set.insert(index, value);
+        }
+
+        set.insert_near(3, 'd', 5);
+        assert_eq!(set.indices().collect::<Vec<_>>(), [5, 3, 1, 8]);
+        set.insert_near(9, 'e', 2);
+        assert_eq!(set.indices().collect::<Vec<_>>(), [5, 3, 1, 8, 9]);
+        for (index, value) in [(5, 'a'), (3, 'd'), (1, 'b'), (8, 'c'), (9, 'e')] {
+            assert_eq!(set.get(index), Some(&value));
+        }
+    }
+
+    #[cfg(feature = "multi_threaded")]
+    #[test]
+    fn sparse_set_par_values_mut() {
+        use bevy_tasks::{ComputeTaskPool, TaskPool};
+        ComputeTaskPool::get_or_init(TaskPool::default);
+
+        let mut set = SparseSet::<u32, u64>::new();
+        for index in 0..1000 {
+            set.insert(index * 3, u64::from(index));
+        }
+        let expected: Vec<u64> = set.values().map(|value| value * 2).collect();
+
+        set.par_values_mut(64, |value| *value *= 2);
+        assert_eq!(set.values().copied().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn sparse_set_mark_and_sweep() {
+        let mut set = SparseSet::<u32, u32>::new();
+        for index in 0..10 {
+            set.insert(index, index * index);
+        }
+
+        let even: Vec<_> = set
+            .iter()
+            .filter(|(_, &value)| value % 2 == 0)
+            .map(|(&index, _)| index)
+            .collect();
+        for index in even {
+            set.mark_for_removal(index);
+        }
+        set.mark_for_removal(4);
+        set.mark_for_removal(20);
+        assert_eq!(set.len(), 10);
+
+        assert_eq!(set.sweep(), 5);
+        let mut survivors: Vec<_> = set.indices().collect();
+        survivors.sort_unstable();
+        assert_eq!(survivors, [1, 3, 5, 7, 9]);
+        for index in survivors {
+            assert_eq!(set.get(index), Some(&(index * index)));
+        }
+        assert_eq!(set.sweep(), 0);
+    }
+
+    #[test]
+    fn sparse_set_clear_range() {
+        let mut set = SparseSet::<u32, u32>::new();
+        for index in 0..50 {
+            set.insert(index, index * 2);
+        }
+
+        assert_eq!(set.clear_range(10..20), 10);
+        assert_eq!(set.len(), 40);
+        for index in 0..50 {
+            if (10..20).contains(&index) {
+                assert_eq!(set.get(index), None);
+            } else {
+                assert_eq!(set.get(index), Some(&(index * 2)));
+            }
+        }
+        assert_eq!(set.clear_range(45..), 5);
+        assert_eq!(set.clear_range(..=100), 35);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn sparse_set_clear_range_max_bounds() {
+        use std::ops::Bound;
+
+        let mut set = SparseSet::<usize, u32>::new();
+        set.insert(3, 3);
+        set.insert(7, 7);
+        assert_eq!(
+            set.clear_range((Bound::Excluded(usize::MAX), Bound::Unbounded)),
+            0
+        );
+        assert_eq!(set.clear_range(5..=usize::MAX), 1);
+        assert_eq!(set.get(3), Some(&3));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn sparse_set_rotate_dense() {
+        let mut set = SparseSet::<u32, char>::new();
+        for (index, value) in [(5, 'a'), (1, 'b'), (8, 'c'), (3, 'd')] {
+            set.insert(index, value);
+        }
+
+        set.rotate_dense(1);
+        assert_eq!(set.indices().collect::<Vec<_>>(), [1, 8, 3, 5]);
+        set.rotate_dense(6);
+        assert_eq!(set.indices().collect::<Vec<_>>(), [3, 5, 1, 8]);
+        for (index, value) in [(5, 'a'), (1, 'b'), (8, 'c'), (3, 'd')] {
+            assert_eq!(set.get(index), Some(&value));
+        }
+    }
+
+    #[test]
+    fn sparse_set_clone() {
+        let mut set = SparseSet::<u32, String>::new();
+        for index in [7, 2, 9, 4] {
+            set.insert(index, index.to_string());
+        }
+        set.remove(2);
+
+        let mut clone = set.clone();
+        assert_eq!(clone.indices().collect::<Vec<_>>(), [7, 4, 9]);
+        assert!(clone.values().eq(set.values()));
+
+        clone.get_mut(7).unwrap().push('!');
+        clone.remove(9);
+        clone.insert(1, "1".to_string());
+        assert_eq!(set.indices().collect::<Vec<_>>(), [7, 4, 9]);
+        assert_eq!(set.get(7).map(String::as_str), Some("7"));
+        assert_eq!(set.get(9).map(String::as_str), Some("9"));
+        assert!(!set.contains(1));
+
+        let immutable = set.into_immutable();
+        let immutable_clone = immutable.clone();
+        assert!(immutable_clone.iter().eq(immutable.iter()));
+    }
+
+    #[test]
+    fn sparse_set_drain() {
+        let mut set = SparseSet::<u32, String>::new();
+        for index in 0..6 {
+            set.insert(index, index.to_string());
+        }
+
+        let drained: Vec<_> = set.drain().take(3).collect();
+        assert_eq!(drained.len(), 3);
+        for (index, value) in drained {
+            assert_eq!(value, index.to_string());
+        }
+        assert!(set.is_empty());
+        for index in 0..6 {
+            assert!(!set.contains(index));
+        }
+
+        set.insert(4, "four".to_string());
+        assert_eq!(set.get(4).map(String::as_str), Some("four"));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn sparse_set_drain_forgets_marked_removals() {
+        let mut set = SparseSet::<u32, u32>::new();
+        set.insert(3, 30);
+        set.mark_for_removal(3);
+        assert_eq!(set.drain().count(), 1);
+
+        set.insert(3, 31);
+        assert_eq!(set.sweep(), 0);
+        assert_eq!(set.get(3), Some(&31));
+    }
+
+    #[test]
+    fn sparse_set_retain() {
+        fn set_of(indices: &[u32]) -> SparseSet<u32, u32> {
+            let mut set = SparseSet::new();
+            for &index in indices {
+                set.insert(index, index * 10);
+            }
+            set
+        }
+
+        // Remove the last element.
+        let mut set = set_of(&[1, 2, 3]);
+        set.retain(|&index, _| index != 3);
+        assert_eq!(set.indices().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(set.get(3), None);
+
+        // Remove the middle element, swapping the last one into its place.
+        let mut set = set_of(&[1, 2, 3]);
+        set.retain(|&index, value| {
+            *value += 1;
+            index != 2
+        });
+        assert_eq!(set.indices().collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(set.get(1), Some(&11));
+        assert_eq!(set.get(3), Some(&31));
+        assert_eq!(set.get(2), None);
+
+        // Remove everything.
+        let mut set = set_of(&[1, 2, 3]);
+        set.retain(|_, _| false);
+        assert!(set.is_empty());
+        for index in 1..=3 {
+            assert_eq!(set.get(index), None);
+        }
+        set.insert(2, 0);
+        assert_eq!(set.get(2), Some(&0));
+    }
+
+    #[test]
+    fn sparse_set_retain_counted() {
+        let mut set = SparseSet::<u32, u32>::default();
+        for i in 0..10 {
+            set.insert(i, i * 10);
+        }
+
+        assert_eq!(set.retain_counted(|key, _| key % 3 != 0), 4);
+        assert_eq!(set.len(), 6);
+        for i in 0..10 {
+            assert_eq!(set.get(i), (i % 3 != 0).then_some(&(i * 10)));
+        }
+        assert_eq!(set.retain_counted(|_, _| true), 0);
+        assert_eq!(set.retain_counted(|_, _| false), 6);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn paged_sparse_array_small_pages() {
+        let mut array = PagedSparseArray::<u32, u32, 16>::new();
+        for index in [0, 15, 16, 1_000_000] {
+            array.insert(index, index + 1);
+        }
+
+        for index in [0, 15, 16, 1_000_000] {
+            assert_eq!(array.get(index), Some(&(index + 1)));
+        }
+        assert_eq!(array.get(1), None);
+        assert_eq!(array.get(999_999), None);
+        assert_eq!(array.get(2_000_000), None);
+        let allocated: Vec<_> = (0..array.pages.len())
+            .filter(|&page| !array.pages[page].is_empty())
+            .collect();
+        assert_eq!(allocated, [0, 1, 1_000_000 / 16]);
+
+        assert_eq!(array.remove(15), Some(16));
+        assert!(!array.contains(15));
+        assert!(array.contains(0));
+    }
+
+    #[test]
+    fn paged_sparse_array_large_pages() {
+        let mut array = PagedSparseArray::<u32, u32, 4096>::new();
+        for index in [0, 4095, 4096, 1_000_000] {
+            array.insert(index, index + 1);
+        }
+
+        for index in [0, 4095, 4096, 1_000_000] {
+            assert_eq!(array.get(index), Some(&(index + 1)));
+        }
+        assert_eq!(array.get(4097), None);
+        let allocated: Vec<_> = (0..array.pages.len())
+            .filter(|&page| !array.pages[page].is_empty())
+            .collect();
+        assert_eq!(allocated, [0, 1, 1_000_000 / 4096]);
+        *array.get_mut(4096).unwrap() = 0;
+        assert_eq!(array.get(4096), Some(&0));
+    }
+
+    #[test]
+    fn sparse_array_diff() {
+        let mut previous = SparseArray::<u32, char>::new();
+        previous.insert(0, 'a');
+        previous.insert(2, 'b');
+        previous.insert(3, 'c');
+        previous.insert(8, 'd');
+        let mut current = SparseArray::<u32, char>::new();
+        current.insert(0, 'a');
+        current.insert(2, 'x');
+        current.insert(3, 'c');
+        current.insert(5, 'e');
+
+        let changes: Vec<_> = current.diff(&previous).collect();
+        assert_eq!(
+            changes,
+            [
+                SparseArrayChange::Changed {
+                    index: 2,
+                    previous: &'b',
+                    value: &'x'
+                },
+                SparseArrayChange::Added {
+                    index: 5,
+                    value: &'e'
+                },
+                SparseArrayChange::Removed {
+                    index: 8,
+                    previous: &'d'
+                },
+            ]
+        );
+        assert_eq!(previous.diff(&previous).count(), 0);
+    }
+
+    #[test]
+    fn sparse_array_presence_mask() {
+        let mut array = SparseArray::<u32, char>::new();
+        array.insert(1, 'a');
+        array.insert(6, 'b');
+        array.insert(3, 'c');
+        array.remove(6);
+
+        let mut mask = array.presence_mask();
+        for index in 0..10 {
+            assert_eq!(mask.contains(index), array.contains(index));
+        }
+        assert_eq!(mask.iter_present().collect::<Vec<_>>(), [1, 3]);
+
+        mask.insert(12);
+        mask.remove(1);
+        assert_eq!(mask.iter_present().collect::<Vec<_>>(), [3, 12]);
+    }
+
+    #[test]
+    fn sparse_array_pages_bound_memory() {
+        let mut array = SparseArray::<u32, u64>::new();
+        array.insert(0, 0);
+        array.insert(1_000_000, 1);
+        assert_eq!(array.get(0), Some(&0));
+        assert_eq!(array.get(1_000_000), Some(&1));
+        assert_eq!(array.get(999_999), None);
+        assert_eq!(array.get(2_000_000), None);
+
+        let flat_bytes = 1_000_001 * std::mem::size_of::<Option<u64>>();
+        assert!(array.heap_bytes() < flat_bytes / 100);
+
+        assert_eq!(array.remove(1_000_000), Some(1));
+        assert!(!array.contains(1_000_000));
+        let immutable = array.into_immutable();
+        assert_eq!(immutable.pages.len(), 1);
+        assert_eq!(immutable.get(0), Some(&0));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "is too large to be stored in a sparse array")]
+    fn sparse_array_index_too_large() {
+        let mut array = SparseArray::<usize, u64>::new();
+        array.insert(usize::MAX / 2, 0);
+    }
+
+    #[test]
+    fn sparse_array_snapshot_restore() {
+        let mut array = SparseArray::<u32, u32>::new();
+        array.insert(0, 0);
+        array.insert(4, 40);
+        array.insert(1_000_000, 7);
+        let snapshot = array.snapshot();
+        // Pages without values are not expanded into slots.
+        assert_eq!(snapshot.len(), array.pages.len());
+        assert_eq!(
+            snapshot.iter().map(|page| page.len()).sum::<usize>(),
+            array.len()
+        );
+
+        array.insert(4, 41);
+        array.remove(0);
+        array.insert(2, 20);
+        array.insert(9, 90);
+        array.remove(1_000_000);
+        let pages = array.pages.as_ptr();
+        let capacity = array.pages[0].capacity();
+
+        array.restore(&snapshot);
+        assert_eq!(array.pages.as_ptr(), pages);
+        assert_eq!(array.pages[0].capacity(), capacity);
+        assert_eq!(array.pages[0].len(), snapshot[0].len());
+        for index in 0..10 {
+            assert_eq!(
+                array.get(index),
+                snapshot[0].get(index as usize).and_then(Option::as_ref)
+            );
+        }
+        assert_eq!(array.get(0), Some(&0));
+        assert_eq!(array.get(4), Some(&40));
+        assert_eq!(array.get(1_000_000), Some(&7));
+    }
+
+    #[test]
+    fn sparse_set_parts_round_trip() {
+        let mut set = SparseSet::<u32, char>::new();
+        set.insert(3, 'a');
+        set.insert(0, 'b');
+        set.insert(7, 'c');
+        set.remove(3);
+
+        let (indices, dense, sparse) = set.into_parts();
+        let set = SparseSet::from_parts(indices, dense, sparse).unwrap();
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.get(0), Some(&'b'));
+        assert_eq!(set.get(7), Some(&'c'));
+        assert_eq!(set.get(3), None);
+    }
+
+    #[test]
+    fn sparse_array_get_disjoint() {
+        let mut array = SparseArray::<u32, char>::default();
+        array.insert(1, 'a');
+        array.insert(4, 'b');
+        array.insert(9, 'c');
+
+        assert_eq!(
+            array.get_disjoint([4, 2, 9, 100]),
+            [Some(&'b'), None, Some(&'c'), None]
+        );
+    }
+
+    #[test]
+    fn sparse_array_shrink() {
+        let mut array = SparseArray::<u32, char>::default();
+        array.insert(0, 'a');
+        array.insert(500, 'b');
+        array.remove(500);
+        assert_eq!(array.pages[0].len(), 501);
+
+        array.shrink();
+        assert_eq!(array.pages[0].len(), 1);
+        assert_eq!(array.pages[0].capacity(), 1);
+        assert_eq!(array.get(0), Some(&'a'));
+        assert_eq!(array.get(500), None);
+
+        array.insert(2500, 'c');
+        array.remove(2500);
+        array.shrink();
+        assert_eq!(array.pages.len(), 1);
+    }
+
+    #[test]
+    fn sparse_array_iter() {
+        let mut array = SparseArray::<u32, char>::default();
+        array.insert(2000, 'c');
+        array.insert(5, 'a');
+        array.insert(700, 'b');
+        array.insert(9, 'x');
+        array.remove(9);
+
+        assert_eq!(
+            array.iter().collect::<Vec<_>>(),
+            [(5, &'a'), (700, &'b'), (2000, &'c')]
+        );
+    }
+
+    #[test]
+    fn sparse_set_get_or_insert_default() {
+        let mut counts = SparseSet::<u32, u64>::new();
+        for index in [3, 1, 3, 3] {
+            *counts.get_or_insert_default(index) += 1;
+        }
+        assert_eq!(counts.get(3), Some(&3));
+        assert_eq!(counts.get(1), Some(&1));
+        assert_eq!(*counts.get_or_insert_default(8), 0);
+        assert_eq!(counts.len(), 3);
+    }
+
+    #[test]
+    fn sparse_set_shift_remove() {
+        let mut set = SparseSet::<u32, char>::new();
+        for (index, value) in [(4, 'a'), (0, 'b'), (9, 'c'), (2, 'd'), (7, 'e')] {
+            set.insert(index, value);
+        }
+
+        assert_eq!(set.shift_remove(9), Some('c'));
+        assert_eq!(set.shift_remove(9), None);
+        assert_eq!(
+            set.iter().map(|(&i, &v)| (i, v)).collect::<Vec<_>>(),
+            [(4, 'a'), (0, 'b'), (2, 'd'), (7, 'e')]
+        );
+        for (index, value) in [(4, 'a'), (0, 'b'), (2, 'd'), (7, 'e')] {
+            assert_eq!(set.get(index), Some(&value));
+        }
+        assert!(!set.contains(9));
+
+        assert_eq!(set.shift_remove(7), Some('e'));
+        assert_eq!(set.indices().collect::<Vec<_>>(), [4, 0, 2]);
+    }
+
+    #[test]
+    fn sparse_set_values_slice() {
+        let mut set: SparseSet<u32, f32> = (0..10).map(|i| (i * 3, i as f32)).collect();
+        set.remove(6);
+        set.remove(15);
+
+        let sum: f32 = set.values_slice().iter().sum();
+        assert_eq!(sum, set.values().sum::<f32>());
+        assert_eq!(set.values_slice().len(), set.len());
+
+        for value in set.values_slice_mut() {
+            *value *= 2.0;
+        }
+        assert_eq!(set.get(9), Some(&6.0));
+        assert_eq!(set.values().sum::<f32>(), sum * 2.0);
+    }
+
+    #[test]
+    fn next_dense_index_in_range() {
+        assert_eq!(next_dense_index(0).get(), 0);
+        assert_eq!(next_dense_index(usize::MAX - 1).get(), usize::MAX - 1);
+        assert_eq!(next_table_row(u32::MAX as usize - 1).as_u32(), u32::MAX - 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "sparse set exceeded usize::MAX - 1 entries")]
+    fn next_dense_index_overflow() {
+        next_dense_index(usize::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "sparse set exceeded u32::MAX entries")]
+    fn next_table_row_overflow() {
+        next_table_row(u32::MAX as usize);
+    }
+
+    #[test]
+    fn sparse_set_get_many() {
+        let mut set = SparseSet::<u32, char>::new();
+        set.insert(2, 'a');
+        set.insert(6, 'b');
+
+        let [first, absent, second, repeated] = set.get_many([2, 3, 6, 2]);
+        assert_eq!(first, Some(&'a'));
+        assert_eq!(absent, None);
+        assert_eq!(second, Some(&'b'));
+        assert!(std::ptr::eq(first.unwrap(), repeated.unwrap()));
+        assert_eq!(set.get_many([]), [None::<&char>; 0]);
+    }
+
+    #[test]
+    fn sparse_set_sparse_len() {
+        let mut set = SparseSet::<u32, char>::new();
+        assert_eq!(set.sparse_len(), 0);
+        set.insert(0, 'a');
+        set.insert(100, 'b');
+        assert_eq!(set.len(), 2);
+        assert!(set.sparse_len() >= 101);
+    }
+
+    #[test]
+    fn sparse_set_swap() {
+        let mut set = SparseSet::<u32, char>::new();
+        set.insert(5, 'a');
+        set.insert(1, 'b');
+        set.insert(9, 'c');
+
+        set.swap(5, 9);
+        assert_eq!(set.indices().collect::<Vec<_>>(), [9, 1, 5]);
+        assert_eq!(set.values().copied().collect::<Vec<_>>(), ['c', 'b', 'a']);
+        assert_eq!(set.get(5), Some(&'a'));
+        assert_eq!(set.get(1), Some(&'b'));
+        assert_eq!(set.get(9), Some(&'c'));
+
+        set.swap(1, 1);
+        assert_eq!(set.indices().collect::<Vec<_>>(), [9, 1, 5]);
+        assert_eq!(set.remove(9), Some('c'));
+        assert_eq!(set.get(5), Some(&'a'));
+    }
+
+    #[test]
+    #[should_panic(expected = "no value at sparse set index 4")]
+    fn sparse_set_swap_missing() {
+        let mut set = SparseSet::<u32, char>::new();
+        set.insert(5, 'a');
+        set.swap(5, 4);
+    }
+
+    #[test]
+    fn sparse_set_eq() {
+        let mut a = SparseSet::<u32, i32>::new();
+        a.insert(1, -1);
+        a.insert(2, -2);
+        a.insert(3, -3);
+        a.remove(1);
+
+        let mut b = SparseSet::<u32, i32>::new();
+        b.insert(2, -2);
+        b.insert(9, 0);
+        b.insert(3, -3);
+        b.remove(9);
+        assert_ne!(
+            a.indices().collect::<Vec<_>>(),
+            b.indices().collect::<Vec<_>>()
+        );
+        assert_eq!(a, b);
+
+        b.insert(2, 2);
+        assert_ne!(a, b);
+        b.insert(2, -2);
+        b.insert(4, -4);
+        assert_ne!(a, b);
+        assert_ne!(b, a);
+    }
+
+    #[test]
+    fn sparse_set_index_ops() {
+        let mut set = SparseSet::<u32, i32>::new();
+        set.insert(3, 30);
+        set.insert(7, 70);
+        assert_eq!(set[3], 30);
+        set[7] += 1;
+        assert_eq!(set[7], 71);
+    }
+
+    #[test]
+    #[should_panic(expected = "no value at sparse set index 5")]
+    fn sparse_set_index_missing() {
+        let mut set = SparseSet::<u32, i32>::new();
+        set.insert(3, 30);
+        let _ = set[5];
+    }
+
+    #[test]
+    fn sparse_set_iter_sorted() {
+        let mut set = SparseSet::<u32, i32>::new();
+        for index in [9, 2, 14, 0, 5, 11] {
+            set.insert(index, -(index as i32));
+        }
+        set.remove(9);
+        set.remove(0);
+        set.insert(1, -1);
+        assert_ne!(set.indices().collect::<Vec<_>>(), [1, 2, 5, 11, 14]);
+
+        let sorted: Vec<_> = set.iter_sorted().map(|(&i, &v)| (i, v)).collect();
+        assert_eq!(sorted, [(1, -1), (2, -2), (5, -5), (11, -11), (14, -14)]);
+    }
+
+    #[test]
+    fn sparse_set_intersection_and_difference_keys() {
+        let visible: SparseSet<u32, ()> = [1, 2, 3, 4, 5].into_iter().map(|i| (i, ())).collect();
+        let selected: SparseSet<u32, &str> = [(4, "d"), (2, "b"), (8, "h")].into_iter().collect();
+        let sorted = |iter: &mut dyn Iterator<Item = u32>| {
+            let mut keys: Vec<_> = iter.collect();
+            keys.sort_unstable();
+            keys
+        };
+
+        assert_eq!(sorted(&mut visible.intersection_keys(&selected)), [2, 4]);
+        assert_eq!(sorted(&mut selected.intersection_keys(&visible)), [2, 4]);
+        assert_eq!(sorted(&mut visible.difference_keys(&selected)), [1, 3, 5]);
+        assert_eq!(sorted(&mut selected.difference_keys(&visible)), [8]);
+
+        let disjoint: SparseSet<u32, ()> = [6, 7].into_iter().map(|i| (i, ())).collect();
+        assert_eq!(visible.intersection_keys(&disjoint).count(), 0);
+        assert_eq!(sorted(&mut disjoint.difference_keys(&visible)), [6, 7]);
+
+        let empty = SparseSet::<u32, ()>::new();
+        assert_eq!(visible.intersection_keys(&empty).count(), 0);
+        assert_eq!(visible.difference_keys(&empty).count(), 5);
+        assert_eq!(empty.difference_keys(&visible).count(), 0);
+    }
+
+    #[test]
+    fn sparse_set_from_iter_and_extend() {
+        let mut set: SparseSet<u32, char> =
+            vec![(4, 'a'), (1, 'b'), (4, 'c')].into_iter().collect();
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.get(4), Some(&'c'));
+        assert_eq!(set.get(1), Some(&'b'));
+        assert_eq!(set.indices().collect::<Vec<_>>(), [4, 1]);
+
+        set.extend([(7, 'd'), (1, 'e')]);
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.get(7), Some(&'d'));
+        assert_eq!(set.get(1), Some(&'e'));
+        assert_eq!(set.indices().collect::<Vec<_>>(), [4, 1, 7]);
+    }
+
+    #[test]
+    fn sparse_set_iter_copied_mut() {
+        let mut set = SparseSet::<u32, u32>::new();
+        for index in [3, 1, 7] {
+            set.insert(index, 0);
+        }
+
+        for (index, value) in set.iter_copied_mut() {
+            *value = index * 10;
+        }
+        assert_eq!(set.get(3), Some(&30));
+        assert_eq!(set.get(1), Some(&10));
+        assert_eq!(set.get(7), Some(&70));
+    }
+
+    #[test]
+    fn sparse_set_get_disjoint_mut() {
+        let mut set = SparseSet::<Entity, u32>::new();
+        let (e0, e1, e2) = (
+            Entity::from_raw(0),
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+        );
+        set.insert(e0, 10);
+        set.insert(e1, 20);
+
+        let [a, b] = set.get_disjoint_mut([e1, e0]);
+        let (a, b) = (a.unwrap(), b.unwrap());
+        std::mem::swap(a, b);
+        assert_eq!(set.get(e0), Some(&20));
+        assert_eq!(set.get(e1), Some(&10));
+
+        let [a, missing] = set.get_disjoint_mut([e0, e2]);
+        *a.unwrap() += 1;
+        assert!(missing.is_none());
+        assert_eq!(set.get(e0), Some(&21));
+    }
+
+    #[test]
+    #[should_panic]
+    fn sparse_set_get_disjoint_mut_repeated_index() {
+        let mut set = SparseSet::<u32, u32>::new();
+        set.insert(3, 30);
+        set.insert(5, 50);
+        set.get_disjoint_mut([3, 5, 3]);
+    }
+
+    #[test]
+    fn sparse_set_reserve() {
+        let mut set = SparseSet::<u32, u32>::new();
+        set.insert(0, 0);
+        set.reserve(10);
+        assert!(set.capacity() >= 11);
+        set.reserve_exact(20);
+        assert!(set.capacity() >= 21);
+        set.try_reserve(40).unwrap();
+        assert!(set.capacity() >= 41);
+        assert!(set.try_reserve(usize::MAX).is_err());
+
+        let dense = set.dense.as_ptr();
+        let indices = set.indices.as_ptr();
+        for index in 1..=40 {
+            set.insert(index, index);
+        }
+        assert_eq!(set.dense.as_ptr(), dense);
+        assert_eq!(set.indices.as_ptr(), indices);
+    }
+
+    #[test]
+    fn sparse_set_reserve_sparse_density() {
+        let mut set = SparseSet::<u32, u32>::new();
+        set.reserve_sparse_density(50, 4900);
+
+        let dense = set.dense.as_ptr();
+        let indices = set.indices.as_ptr();
+        let pages = set.sparse.pages.as_ptr();
+        let sparse: Vec<_> = set.sparse.pages.iter().map(Vec::as_ptr).collect();
+        for index in (0..=4900).step_by(100) {
+            set.insert(index, index);
+        }
+        assert_eq!(set.len(), 50);
+        assert_eq!(set.dense.as_ptr(), dense);
+        assert_eq!(set.indices.as_ptr(), indices);
+        assert_eq!(set.sparse.pages.as_ptr(), pages);
+        assert!(set.sparse.pages.iter().map(Vec::as_ptr).eq(sparse));
+    }
+
+    #[test]
+    fn sparse_set_index_round_trip() {
+        fn round_trip<I: SparseSetIndex + std::fmt::Debug>(index: I, expected: usize) {
+            assert_eq!(index.sparse_set_index(), expected);
+            assert_eq!(I::get_sparse_set_index(expected), index);
+        }
+
+        round_trip(Entity::from_raw(7), 7);
+        round_trip(NonMaxU32::new(42).unwrap(), 42);
+        round_trip(NonMaxU32::new(u32::MAX - 1).unwrap(), u32::MAX as usize - 1);
+        round_trip(NonMaxUsize::new(0).unwrap(), 0);
+        round_trip(NonMaxUsize::new(1234).unwrap(), 1234);
+
+        let mut set = SparseSet::<NonMaxU32, char>::new();
+        set.insert(NonMaxU32::new(3).unwrap(), 'a');
+        assert_eq!(set.get(NonMaxU32::new(3).unwrap()), Some(&'a'));
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_max_sparse_set_index_rejects_max() {
+        NonMaxUsize::get_sparse_set_index(usize::MAX);
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(target_pointer_width = "64")]
+    fn non_max_u32_sparse_set_index_rejects_out_of_range() {
+        NonMaxU32::get_sparse_set_index(u32::MAX as usize + 1);
+    }
+
+    #[test]
+    fn sparse_set_map_keys() {
+        let mut set = SparseSet::<Entity, char>::new();
+        for (index, value) in [(40, 'a'), (7, 'b'), (1000, 'c')] {
+            set.insert(Entity::from_raw(index), value);
+        }
+
+        let ids = [40, 7, 1000];
+        let set = set.map_keys(|entity| ids.iter().position(|&id| id == entity.index()).unwrap());
+        assert_eq!(set.indices().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(set.get(0), Some(&'a'));
+        assert_eq!(set.get(1), Some(&'b'));
+        assert_eq!(set.get(2), Some(&'c'));
+        assert_eq!(set.get(3), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sparse_set_map_keys_not_injective() {
+        let mut set = SparseSet::<u32, char>::new();
+        set.insert(1, 'a');
+        set.insert(2, 'b');
+        set.map_keys(|_| 0u32);
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn sparse_set_serde_round_trip() {
+        use serde_test::{assert_tokens, Token};
+
+        let mut set = SparseSet::<u32, char>::new();
+        set.insert(7, 'a');
+        set.insert(2, 'b');
+        set.insert(40, 'c');
+
+        assert_tokens(
+            &set,
+            &[
+                Token::Seq { len: Some(3) },
+                Token::Tuple { len: 2 },
+                Token::U32(7),
+                Token::Char('a'),
+                Token::TupleEnd,
+                Token::Tuple { len: 2 },
+                Token::U32(2),
+                Token::Char('b'),
+                Token::TupleEnd,
+                Token::Tuple { len: 2 },
+                Token::U32(40),
+                Token::Char('c'),
+                Token::TupleEnd,
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn sparse_set_serde_rejects_duplicate_index() {
+        use serde_test::{assert_de_tokens_error, Token};
+
+        assert_de_tokens_error::<SparseSet<u32, char>>(
+            &[
+                Token::Seq { len: Some(2) },
+                Token::Tuple { len: 2 },
+                Token::U32(7),
+                Token::Char('a'),
+                Token::TupleEnd,
+                Token::Tuple { len: 2 },
+                Token::U32(7),
+                Token::Char('b'),
+                Token::TupleEnd,
+                Token::SeqEnd,
+            ],
+            "duplicate index 7",
+        );
+    }
+
+    #[test]
+    fn sparse_set_into_dense_with_keys() {
+        let mut set = SparseSet::<u32, char>::new();
+        for (index, value) in [(5, 'a'), (1, 'b'), (8, 'c'), (3, 'd')] {
+            set.insert(index, value);
+        }
+        set.remove(1);
+        let pairs: Vec<_> = set.iter().map(|(&index, &value)| (index, value)).collect();
+
+        let (values, keys) = set.into_dense_with_keys();
+        assert_eq!(values.len(), keys.len());
+        assert_eq!(keys.into_iter().zip(values).collect::<Vec<_>>(), pairs);
+    }
+
+    #[test]
+    fn sparse_set_parts_mismatch() {
+        let mut set = SparseSet::<u32, char>::new();
+        set.insert(1, 'a');
+        set.insert(2, 'b');
+
+        let (indices, mut dense, sparse) = set.into_parts();
+        dense.pop();
+        assert_eq!(
+            SparseSet::from_parts(indices, dense, sparse).err(),
+            Some(PartsError::LengthMismatch {
+                indices: 2,
+                dense: 1
+            })
+        );
+    }
+
+    #[test]
+    fn sparse_set_parts_unmapped_index() {
+        let mut set = SparseSet::<u32, char>::new();
+        set.insert(1, 'a');
+        set.insert(2, 'b');
+
+        let (mut indices, dense, sparse) = set.into_parts();
+        indices.swap(0, 1);
+        assert_eq!(
+            SparseSet::from_parts(indices, dense, sparse).err(),
+            Some(PartsError::UnmappedIndex(0))
+        );
+    }
+
+    #[test]
+    fn sparse_set_parts_extra_sparse_entries() {
+        let mut set = SparseSet::<u32, char>::new();
+        set.insert(1, 'a');
+        set.insert(2, 'b');
+
+        let (indices, dense, mut sparse) = set.into_parts();
+        sparse.insert(7, NonMaxUsize::new(0).unwrap());
+        assert_eq!(
+            SparseSet::from_parts(indices, dense, sparse).err(),
+            Some(PartsError::ExtraSparseEntries {
+                mapped: 3,
+                dense: 2
+            })
+        );
+    }
+
+    #[test]
+    fn sparse_set_chunks() {
+        let mut set = SparseSet::<u32, u32>::default();
+        for i in 0..10 {
+            set.insert(i * 2, i);
+        }
+        set.remove(4);
+
+        let chunked = set.chunks(3).flatten().collect::<Vec<_>>();
+        assert_eq!(chunked, set.values().collect::<Vec<_>>());
+        assert!(set.chunks(3).all(|chunk| chunk.len() <= 3));
+
+        let keys = set.key_chunks(3).flatten().copied().collect::<Vec<_>>();
+        assert_eq!(keys, set.indices().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sparse_set_shrink_sparse_to_fit() {
+        let mut set = SparseSet::<u32, u32>::with_capacity(4);
+        set.insert(0, 0);
+        set.insert(1, 1);
+        set.insert(1000, 2);
+        set.remove(1000);
+        let dense_capacity = set.dense.capacity();
+        let indices_capacity = set.indices.capacity();
+        assert!(set.sparse.pages[0].capacity() > 1000);
+
+        set.shrink_sparse_to_fit();
+        assert!(set.sparse.pages[0].capacity() < 1000);
+        assert_eq!(set.dense.capacity(), dense_capacity);
+        assert_eq!(set.indices.capacity(), indices_capacity);
+        assert_eq!(set.get(0), Some(&0));
+        assert_eq!(set.get(1), Some(&1));
+        assert_eq!(set.get(1000), None);
+    }
+
+    #[test]
+    fn sparse_set_auto_shrink() {
+        let mut set: SparseSet<u32, u32> = (0..1000).map(|index| (index, index)).collect();
+        set.auto_shrink(true);
+        let capacity = set.capacity();
+
+        // Half of the capacity has to be removed before the first shrink.
+        for index in 0..(capacity / 2) as u32 {
+            set.remove(index);
+        }
+        assert_eq!(set.capacity(), capacity);
+
+        let sparse = set.sparse.pages[0].as_ptr();
+        set.remove(999);
+        assert_eq!(set.capacity(), set.len());
+        // Only the dense storage is shrunk.
+        assert_eq!(set.sparse.pages[0].as_ptr(), sparse);
+        assert_eq!(set.get(998), Some(&998));
+
+        // The count restarts from the shrunk capacity.
+        let shrunk = set.capacity();
+        assert!(set.retain_counted(|&index, _| index % 4 != 0) <= shrunk / 2);
+        assert_eq!(set.capacity(), shrunk);
+        set.retain(|&index, _| index % 4 == 1);
+        assert_eq!(set.capacity(), set.len());
+
+        set.auto_shrink(false);
+        let capacity = set.capacity();
+        set.clear_range(..);
+        assert_eq!(set.capacity(), capacity);
+    }
+
+    #[test]
+    fn sparse_set_shrink_to_fit() {
+        let mut set = SparseSet::<u32, u32>::with_capacity(64);
+        set.insert(0, 0);
+        set.insert(1000, 1);
+        set.remove(1000);
+
+        set.shrink_to_fit();
+        assert!(set.dense.capacity() < 64);
+        assert!(set.indices.capacity() < 64);
+        assert!(set.sparse.pages[0].capacity() < 1000);
+        assert_eq!(set.get(0), Some(&0));
+    }
+
+    #[test]
+    fn sparse_set_get_entry_mut() {
+        let mut set = SparseSet::<Entity, Foo>::default();
+        let e0 = Entity::from_raw(0);
+        let e1 = Entity::from_raw(1);
+        set.insert(e0, Foo(0));
+        set.insert(e1, Foo(1));
+
+        let stored_key: *const Entity = &set.indices[1];
+        let (key, value) = set.get_entry_mut(e1).unwrap();
+        assert!(std::ptr::eq(key, stored_key));
+        assert_eq!(*key, e1);
+        value.0 = 10;
+        assert_eq!(set.get(e1), Some(&Foo(10)));
+        assert!(set.get_entry_mut(Entity::from_raw(2)).is_none());
+    }
+
+    #[test]
+    fn sparse_set_fixed() {
+        let mut set = SparseSet::<u32, u32>::fixed(9);
+        assert_eq!(set.sparse.pages[0].len(), 10);
+        set.insert(0, 0);
+        set.insert(9, 9);
+        *set.get_or_insert_with(5, || 0) += 5;
+        assert_eq!(set.get(5), Some(&5));
+        assert_eq!(set.sparse.pages[0].len(), 10);
+    }
+
+    #[test]
+    fn sparse_set_fixed_across_pages() {
+        let mut set = SparseSet::<u32, u32>::fixed(2500);
+        let pages: Vec<_> = set.sparse.pages.iter().map(Vec::as_ptr).collect();
+        assert_eq!(
+            set.sparse.pages.iter().map(Vec::len).collect::<Vec<_>>(),
+            [1024, 1024, 453]
+        );
+        for index in (0..=2500).step_by(50) {
+            set.insert(index, index);
+        }
+        assert_eq!(set.len(), 51);
+        assert!(set.sparse.pages.iter().map(Vec::as_ptr).eq(pages));
+    }
+
+    #[test]
+    #[should_panic]
+    fn sparse_set_fixed_out_of_bounds() {
+        let mut set = SparseSet::<u32, u32>::fixed(9);
+        set.insert(10, 10);
+    }
+
+    #[test]
+    fn versioned_sparse_set() {
+        let mut set = VersionedSparseSet::<u32, u32>::default();
+        assert_eq!(set.version(0), None);
+
+        set.insert(0, 10);
+        set.insert(1, 20);
+        let v0 = set.version(0).unwrap();
+        let v1 = set.version(1).unwrap();
+
+        // reads don't advance the version
+        assert_eq!(set.get(0), Some(&10));
+        assert_eq!(set.iter().count(), 2);
+        assert_eq!(set.version(0), Some(v0));
+
+        *set.get_mut(0).unwrap() += 1;
+        assert_eq!(set.get(0), Some(&11));
+        assert_ne!(set.version(0), Some(v0));
+        assert_eq!(set.version(1), Some(v1));
+
+        let v0 = set.version(0).unwrap();
+        set.insert(0, 12);
+        assert_ne!(set.version(0), Some(v0));
+
+        assert_eq!(set.remove(0), Some(12));
+        assert_eq!(set.version(0), None);
+    }
+
+    #[test]
+    fn component_sparse_set_density() {
+        #[derive(Component)]
+        struct TestComponent;
+
+        let info = component_info::<TestComponent>(0);
+        let mut set = ComponentSparseSet::new(&info, 0);
+
+        assert_eq!(set.density(0), 0.0);
+        for i in 0..9 {
+            insert(&mut set, Entity::from_raw(i), TestComponent);
+        }
+        assert_eq!(set.density(10), 0.9);
+        assert!(!set.should_migrate_to_table(10, 0.9));
+
+        insert(&mut set, Entity::from_raw(9), TestComponent);
+        assert_eq!(set.density(10), 1.0);
+        assert!(set.should_migrate_to_table(10, 0.9));
+    }
+
+    #[test]
+    fn component_sparse_set_typed_iter() {
+        #[derive(Component, Debug, PartialEq)]
+        struct Health(u32);
+
+        let info = component_info::<Health>(0);
+        let mut set = ComponentSparseSet::new(&info, 0);
+        for i in [3, 1, 4, 5] {
+            insert(&mut set, Entity::from_raw(i), Health(i * 10));
+        }
+        set.remove(Entity::from_raw(1));
+
+        // SAFETY: the set stores `Health` and is not mutated while iterating
+        let pairs = unsafe { set.typed_iter::<Health>() }.collect::<Vec<_>>();
+        assert_eq!(pairs.len(), 3);
+        for (index, health) in pairs {
+            let ptr = set.get(Entity::from_raw(index)).unwrap();
+            // SAFETY: the set stores `Health`
+            assert_eq!(unsafe { ptr.deref::<Health>() }, health);
+        }
+    }
+
+    #[test]
+    fn component_sparse_set_iter_changed_since() {
+        #[derive(Component, Debug, PartialEq)]
+        struct Health(u32);
+
+        let info = component_info::<Health>(0);
+        let mut set = ComponentSparseSet::new(&info, 0);
+        let insert_at = |set: &mut ComponentSparseSet, index: u32, tick: u32| {
+            OwningPtr::make(Health(index * 10), |ptr| {
+                // SAFETY: the sparse set was created with the layout of `Health`
+                unsafe { set.insert(Entity::from_raw(index), ptr, Tick::new(tick)) };
+            });
+        };
+        insert_at(&mut set, 1, 5);
+        insert_at(&mut set, 2, 10);
+        insert_at(&mut set, 3, 15);
+
+        let changed = |set: &ComponentSparseSet, last_run: u32| {
+            let mut changed: Vec<_> = set
+                .iter_changed_since(Tick::new(last_run), Tick::new(20))
+                // SAFETY: the set stores `Health`
+                .map(|(index, ptr)| (index, unsafe { ptr.deref::<Health>() }.0))
+                .collect();
+            changed.sort_unstable();
+            changed
+        };
+        assert_eq!(changed(&set, 8), [(2, 20), (3, 30)]);
+        assert_eq!(changed(&set, 15), []);
+
+        insert_at(&mut set, 1, 18);
+        assert_eq!(changed(&set, 12), [(1, 10), (3, 30)]);
+    }
+
+    #[test]
+    fn sparse_sets_get_or_insert_with_capacity() {
+        #[derive(Component)]
+        struct A(#[allow(dead_code)] u32);
+        #[derive(Component)]
+        struct B(#[allow(dead_code)] u32);
+
+        let a = component_info::<A>(0);
+        let b = component_info::<B>(1);
+        let mut sets = SparseSets::default();
+        assert_eq!(sets.get_or_insert_with_capacity(&a, 1024).capacity(), 1024);
+        assert_eq!(sets.get_or_insert(&b).capacity(), 64);
+        // An existing set is returned as is.
+        assert_eq!(sets.get_or_insert_with_capacity(&b, 1024).capacity(), 64);
+    }
+
+    #[test]
+    fn sparse_sets_remove() {
+        #[derive(Component)]
+        struct A;
+        #[derive(Component)]
+        struct B;
+        #[derive(Component)]
+        struct C;
+
+        let a = component_info::<A>(0);
+        let b = component_info::<B>(1);
+        let c = component_info::<C>(2);
+        let mut sets = SparseSets::default();
+        insert(sets.get_or_insert(&a), Entity::from_raw(0), A);
+        sets.get_or_insert(&b);
+        sets.get_or_insert(&c);
+        assert_eq!(sets.len(), 3);
+
+        let removed = sets.remove(a.id()).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(sets.len(), 2);
+        assert!(sets.get(a.id()).is_none());
+        assert!(sets.remove(a.id()).is_none());
+
+        // The remaining sets keep their order, and are found at their new positions.
+        let ids: Vec<_> = sets.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, [b.id(), c.id()]);
+        assert!(sets.get_mut(b.id()).is_some());
+        assert!(sets.get_mut(c.id()).is_some());
+    }
+
+    #[test]
+    fn sparse_sets_remove_entity() {
+        #[derive(Component)]
+        struct Marker;
+
+        let mut sets = SparseSets::default();
+        let (e0, e1) = (Entity::from_raw(0), Entity::from_raw(1));
+        let infos = [0, 1, 2].map(component_info::<Marker>);
+        insert(sets.get_or_insert(&infos[0]), e0, Marker);
+        sets.get_or_insert(&infos[1]);
+        insert(sets.get_or_insert(&infos[2]), e0, Marker);
+        insert(sets.get_or_insert(&infos[2]), e1, Marker);
+
+        assert_eq!(sets.remove_entity(e0), 2);
+        assert!(sets.iter().all(|(_, set)| !set.contains(e0)));
+        assert!(sets.get(ComponentId::new(2)).unwrap().contains(e1));
+        assert_eq!(sets.remove_entity(e0), 0);
+    }
+
+    #[test]
+    fn sparse_sets_iter_follows_registration_order() {
+        #[derive(Component)]
+        struct Marker;
+
+        let register = || {
+            let mut sets = SparseSets::default();
+            for id in [5, 2, 9, 0] {
+                let info = component_info::<Marker>(id);
+                sets.get_or_insert(&info);
+            }
+            sets.iter().map(|(id, _)| id.index()).collect::<Vec<_>>()
+        };
+
+        let first = register();
+        assert_eq!(first, [5, 2, 9, 0]);
+        assert_eq!(register(), first);
+    }
+
+    #[test]
+    fn sparse_sets_iter_mut() {
+        #[derive(Component)]
+        struct Marker;
+
+        let mut sets = SparseSets::default();
+        for id in [4, 1, 7] {
+            let info = component_info::<Marker>(id);
+            sets.get_or_insert(&info);
+        }
+
+        let mut visited = Vec::new();
+        for (id, set) in sets.iter_mut() {
+            insert(set, Entity::from_raw(id.index() as u32), Marker);
+            visited.push(id.index());
+        }
+        assert_eq!(visited, [4, 1, 7]);
+        for id in [4, 1, 7] {
+            let set = sets.get(ComponentId::new(id)).unwrap();
+            assert_eq!(set.len(), 1);
+            assert!(set.contains(Entity::from_raw(id as u32)));
+        }
+    }
+
+    #[test]
+    fn component_sparse_set_shrink_to_fit() {
+        #[derive(Component, Debug, PartialEq)]
+        struct Name(String);
+
+        let info = component_info::<Name>(0);
+        let mut set = ComponentSparseSet::new(&info, 0);
+        for index in 0..100 {
+            insert(&mut set, Entity::from_raw(index), Name(index.to_string()));
+        }
+        for index in 0..95 {
+            set.remove(Entity::from_raw(index));
+        }
+        assert!(set.capacity() >= 100);
+
+        set.shrink_to_fit();
+        assert_eq!(set.capacity(), 5);
+        assert_eq!(set.entities.capacity(), 5);
+        assert_eq!(set.sparse.pages[0].capacity(), 100);
+        for index in 95..100 {
+            // SAFETY: the set stores `Name`
+            let name = unsafe { set.get(Entity::from_raw(index)).unwrap().deref::<Name>() };
+            assert_eq!(name, &Name(index.to_string()));
+        }
+
+        for index in 95..100 {
+            set.remove(Entity::from_raw(index));
+        }
+        set.shrink_to_fit();
+        assert_eq!(set.capacity(), 0);
+        assert!(set.sparse.pages.is_empty());
+        insert(&mut set, Entity::from_raw(3), Name("again".to_string()));
+        assert!(set.contains(Entity::from_raw(3)));
+    }
+
+    #[test]
+    fn component_sparse_set_heap_bytes() {
+        #[derive(Component)]
+        struct Value(#[allow(dead_code)] u64);
+
+        let info = component_info::<Value>(0);
+        let mut sets = SparseSets::default();
+        let set = sets.get_or_insert_with_capacity(&info, 0);
+        let empty = set.heap_bytes();
+        for index in 0..1000 {
+            insert(set, Entity::from_raw(index), Value(index.into()));
+        }
+        let full = set.heap_bytes();
+        // Each value also has an added and a changed tick.
+        let value_bytes = std::mem::size_of::<u64>() + 2 * std::mem::size_of::<Tick>();
+        assert!(full >= empty + 1000 * value_bytes);
+        assert_eq!(sets.total_heap_bytes(), full);
+
+        let set = sets.get_mut(info.id()).unwrap();
+        set.clear();
+        set.shrink_to_fit();
+        assert!(set.heap_bytes() < full);
+        assert_eq!(set.heap_bytes(), empty);
+    }
+
+    #[test]
+    fn component_sparse_set_clear_keeps_capacity() {
+        #[derive(Component)]
+        struct Value(#[allow(dead_code)] u64);
+
+        let info = component_info::<Value>(0);
+        let mut set = ComponentSparseSet::new(&info, 0);
+        for index in 0..64 {
+            insert(&mut set, Entity::from_raw(index), Value(index.into()));
+        }
+        let capacity = set.capacity();
+        let heap_bytes = set.heap_bytes();
+
+        for _ in 0..3 {
+            set.clear();
+            assert!(set.is_empty());
+            assert_eq!(set.capacity(), capacity);
+            for index in 0..64 {
+                insert(&mut set, Entity::from_raw(index), Value(index.into()));
+            }
+            assert_eq!(set.capacity(), capacity);
+            assert_eq!(set.heap_bytes(), heap_bytes);
+        }
+
+        set.clear();
+        set.shrink_to_fit();
+        assert!(set.is_empty());
+        assert_eq!(set.capacity(), 0);
+        assert_eq!(set.heap_bytes(), 0);
+        insert(&mut set, Entity::from_raw(3), Value(3));
+        assert!(set.contains(Entity::from_raw(3)));
+    }
+
+    #[test]
+    fn component_sparse_set_iter_entities() {
+        #[derive(Component)]
+        struct Marker;
+
+        let info = component_info::<Marker>(0);
+        let mut set = ComponentSparseSet::new(&info, 0);
+        for index in [4, 9, 2] {
+            insert(&mut set, Entity::from_raw(index), Marker);
+        }
+        set.remove(Entity::from_raw(4));
+
+        let entities: Vec<_> = set.iter_entities().collect();
+        assert_eq!(entities.len(), set.len());
+        assert_eq!(entities, [2, 9]);
+    }
+
+    #[test]
+    fn component_sparse_set_freeze() {
+        #[derive(Component, Debug, PartialEq)]
+        struct Value(u32);
+
+        let info = component_info::<Value>(0);
+        let mut set = ComponentSparseSet::new(&info, 0);
+        let entities = [3, 8, 1].map(Entity::from_raw);
+        for entity in entities {
+            insert(&mut set, entity, Value(entity.index() * 10));
+        }
+
+        let frozen = set.freeze();
+        assert_eq!(frozen.len(), 3);
+        assert!(!frozen.contains(Entity::from_raw(2)));
+
+        std::thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    for entity in entities {
+                        assert!(frozen.contains(entity));
+                        // SAFETY: the set stores `Value`
+                        let value = unsafe { frozen.get(entity).unwrap().deref::<Value>() };
+                        assert_eq!(value, &Value(entity.index() * 10));
+                    }
+                    let indices: Vec<_> = frozen.iter().map(|(index, _)| index).collect();
+                    assert_eq!(indices, [3, 8, 1]);
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn component_sparse_set_toggle_reuses_capacity() {
+        #[derive(Component)]
+        struct Value(u32);
+
+        let info = component_info::<Value>(0);
+        let mut set = ComponentSparseSet::new(&info, 0);
+        for index in 0..8 {
+            insert(&mut set, Entity::from_raw(index), Value(index));
+        }
+        let dense_capacity = set.dense.capacity();
+        let entities_capacity = set.entities.capacity();
+
+        for round in 0..100 {
+            for index in [1, 4, 6] {
+                assert!(set.remove(Entity::from_raw(index)));
+            }
+            for index in [6, 1, 4] {
+                insert(&mut set, Entity::from_raw(index), Value(index + round));
+            }
+        }
+        assert_eq!(set.len(), 8);
+        assert_eq!(set.dense.capacity(), dense_capacity);
+        assert_eq!(set.entities.capacity(), entities_capacity);
+        let ptr = set.get(Entity::from_raw(4)).unwrap();
+        // SAFETY: the sparse set stores `Value`
+        assert_eq!(unsafe { ptr.deref::<Value>() }.0, 103);
+    }
+
+    #[test]
+    fn component_sparse_set_layout_matches() {
+        #[derive(Component)]
+        struct Small(#[allow(dead_code)] u8);
+        #[derive(Component)]
+        struct Large(#[allow(dead_code)] u64);
+        #[derive(Component)]
+        struct SmallNeedsDrop(#[allow(dead_code)] u8);
+
+        impl Drop for SmallNeedsDrop {
+            fn drop(&mut self) {}
+        }
+
+        let small = component_info::<Small>(0);
+        let set = ComponentSparseSet::new(&small, 0);
+        assert!(set.layout_matches(&small));
+        assert!(!set.layout_matches(&component_info::<Large>(1)));
+        assert!(!set.layout_matches(&component_info::<SmallNeedsDrop>(2)));
+    }
+
+    #[test]
+    fn component_sparse_set_get_drop() {
+        #[derive(Component)]
+        struct Position;
+        #[derive(Component)]
+        struct NeedsDrop;
+
+        impl Drop for NeedsDrop {
+            fn drop(&mut self) {}
+        }
+
+        let info = component_info::<Position>(0);
+        assert!(ComponentSparseSet::new(&info, 0).get_drop().is_none());
+        let info = component_info::<NeedsDrop>(1);
+        assert!(ComponentSparseSet::new(&info, 0).get_drop().is_some());
+    }
+
+    #[test]
+    fn component_sparse_set_check_change_ticks_after_older_write() {
+        #[derive(Component)]
+        struct Marker;
+
+        let info = component_info::<Marker>(0);
+        let mut set = ComponentSparseSet::new(&info, 0);
+        let entity = Entity::from_raw(0);
+        insert(&mut set, entity, Marker);
+        set.check_change_ticks(Tick::new(10));
+
+        // Ticks can be set to any value through the cells handed out by the sparse set, as
+        // `Mut::set_last_changed` does, including ones older than any checked before.
+        let changed = set.get_changed_tick(entity).unwrap();
+        // SAFETY: there are no other references to the tick.
+        unsafe { *changed.get() = Tick::new(u32::MAX - 100) };
+
+        set.check_change_ticks(Tick::MAX);
+        let changed = set.get_changed_tick(entity).unwrap();
+        // SAFETY: there are no other references to the tick.
+        assert_eq!(unsafe { changed.get().read() }.get(), 0);
+    }
+
+    #[test]
+    fn sparse_sets_split_disjoint() {
+        #[derive(Component)]
+        struct Health(u32);
+        #[derive(Component)]
+        struct Armor(u32);
+
+        let health = component_info::<Health>(0);
+        let armor = component_info::<Armor>(1);
+        let mut sets = SparseSets::default();
+        for index in 0..4 {
+            insert(
+                sets.get_or_insert(&health),
+                Entity::from_raw(index),
+                Health(index),
+            );
+            insert(
+                sets.get_or_insert(&armor),
+                Entity::from_raw(index),
+                Armor(10 * index),
+            );
+        }
+
+        let health_id = health.id();
+        let handles = sets.split_disjoint(&[health_id, ComponentId::new(2), armor.id()]);
+        assert_eq!(handles.len(), 2);
+        let totals: Vec<u32> = std::thread::scope(|scope| {
+            let workers: Vec<_> = handles
+                .iter()
+                .map(|&handle| {
+                    scope.spawn(move || {
+                        (0..4)
+                            .map(|index| {
+                                let ptr = handle.get(Entity::from_raw(index)).unwrap();
+                                if handle.component_id() == health_id {
+                                    // SAFETY: the sparse set stores `Health`
+                                    unsafe { ptr.deref::<Health>() }.0
+                                } else {
+                                    // SAFETY: the sparse set stores `Armor`
+                                    unsafe { ptr.deref::<Armor>() }.0
+                                }
+                            })
+                            .sum()
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+        assert_eq!(totals, [6, 60]);
+    }
+
+    #[test]
+    fn sparse_sets_clear_entities_where() {
+        #[derive(Component)]
+        struct Transient;
+        #[derive(Component)]
+        struct Persistent;
+
+        let transient = component_info::<Transient>(0);
+        let persistent = component_info::<Persistent>(1);
+        let mut sets = SparseSets::default();
+        insert(
+            sets.get_or_insert(&transient),
+            Entity::from_raw(0),
+            Transient,
+        );
+        insert(
+            sets.get_or_insert(&persistent),
+            Entity::from_raw(0),
+            Persistent,
+        );
+
+        sets.clear_entities_where(|id| id == transient.id());
+        assert!(sets.get(transient.id()).unwrap().is_empty());
+        assert!(sets
+            .get(persistent.id())
+            .unwrap()
+            .contains(Entity::from_raw(0)));
+    }
+
+    #[test]
+    fn sparse_sets_compact_registry() {
+        #[derive(Component)]
+        struct Marker;
+
+        let mut sets = SparseSets::default();
+        for id in [0, 1000, 2000] {
+            let info = component_info::<Marker>(id);
+            sets.get_or_insert(&info);
+        }
+        sets.remove(ComponentId::new(2000));
+        sets.remove(ComponentId::new(1000));
+        assert_eq!(sets.sets.sparse.pages.len(), 2);
+        assert!(sets.sets.sparse.pages[0].capacity() > 1000);
+
+        sets.compact_registry();
+        assert_eq!(sets.sets.sparse.pages.len(), 1);
+        assert!(sets.sets.sparse.pages[0].capacity() < 1000);
+        assert!(sets.sets.dense.capacity() < 3);
+        assert!(sets.get(ComponentId::new(0)).is_some());
+        assert!(sets.get(ComponentId::new(1000)).is_none());
+    }
+
+    #[test]
+    fn sparse_sets_entities_with_any_sparse() {
+        #[derive(Component)]
+        struct A;
+        #[derive(Component)]
+        struct B;
+
+        let a = component_info::<A>(0);
+        let b = component_info::<B>(1);
+        let mut sets = SparseSets::default();
+        for index in [1, 5] {
+            insert(sets.get_or_insert(&a), Entity::from_raw(index), A);
+        }
+        for index in [5, 9] {
+            insert(sets.get_or_insert(&b), Entity::from_raw(index), B);
+        }
+
+        let mut rows = FixedBitSet::new();
+        sets.entities_with_any_sparse(&mut rows);
+        assert_eq!(rows.ones().collect::<Vec<_>>(), [1, 5, 9]);
+    }
+
+    #[test]
+    fn sparse_sets_memory_budget() {
+        let sets = sparse_sets_with_u64s(16);
+        // 16 values with their ticks, 16 entities and 16 sparse slots, at 8 bytes or more each.
+        sets.assert_memory_under(16 * 1024);
+    }
+
+    #[test]
+    #[should_panic(expected = "over the budget of 64 bytes")]
+    fn sparse_sets_memory_budget_exceeded() {
+        let sets = sparse_sets_with_u64s(16);
+        sets.assert_memory_under(64);
+    }
+
+    fn sparse_sets_with_u64s(count: u32) -> SparseSets {
+        #[derive(Component)]
+        struct Large(#[allow(dead_code)] u64);
+
+        let info = component_info::<Large>(0);
+        let mut sets = SparseSets::default();
+        let set = sets.get_or_insert(&info);
+        for index in 0..count {
+            insert(set, Entity::from_raw(index), Large(index.into()));
+        }
+        sets
+    }
+
+    fn component_info<T: Component>(id: usize) -> ComponentInfo {
+        ComponentInfo::new(ComponentId::new(id), ComponentDescriptor::new::<T>())
+    }
+
+    fn insert<T>(set: &mut ComponentSparseSet, entity: Entity, value: T) {
+        OwningPtr::make(value, |ptr| {
+            // SAFETY: the sparse set was created with the layout of `T`
+            unsafe { set.insert(entity, ptr, Tick::new(0)) };
+        });
+    }
+
+    #[test]
+    fn sparse_sets() {
+        let mut sets = SparseSets::default();
+
+        #[derive(Component, Default, Debug)]
+        struct TestComponent1;
+
+        #[derive(Component, Default, Debug)]
+        struct TestComponent2;
+
+        assert_eq!(sets.len(), 0);
+        assert!(sets.is_empty());
 
         init_component::<TestComponent1>(&mut sets, 1);
         assert_eq!(sets.len(), 1);
@@ -720,4 +4292,24 @@ mod tests {
             sets.get_or_insert(&info);
         }
     }
+
+    #[test]
+    fn sparse_sets_get() {
+        #[derive(Component)]
+        struct A;
+        #[derive(Component)]
+        struct B;
+
+        let a = component_info::<A>(1);
+        let b = component_info::<B>(2);
+        let mut sets = SparseSets::default();
+        sets.get_or_insert(&a);
+        sets.get_or_insert(&b);
+
+        insert(sets.get_mut(a.id()).unwrap(), Entity::from_raw(0), A);
+        assert_eq!(sets.get(a.id()).unwrap().len(), 1);
+        assert_eq!(sets.get(b.id()).unwrap().len(), 0);
+        assert!(sets.get(ComponentId::new(3)).is_none());
+        assert!(sets.get_mut(ComponentId::new(3)).is_none());
+    }
 }
@@ -172,6 +172,13 @@ impl Column {
         self.data.layout()
     }
 
+    /// Returns the drop function for elements of the column,
+    /// or `None` if they don't need to be dropped.
+    #[inline]
+    pub fn get_drop(&self) -> Option<unsafe fn(OwningPtr<'_>)> {
+        self.data.get_drop()
+    }
+
     /// Writes component data to the column at given row.
     /// Assumes the slot is uninitialized, drop is not called.
     /// To overwrite existing initialized value, use `replace` instead.
@@ -210,6 +217,19 @@ impl Column {
         self.data.len()
     }
 
+    /// Returns the number of elements the column can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// Returns the number of bytes allocated for the added and changed ticks of the column.
+    #[inline]
+    pub(crate) fn ticks_heap_bytes(&self) -> usize {
+        (self.added_ticks.capacity() + self.changed_ticks.capacity())
+            * std::mem::size_of::<UnsafeCell<Tick>>()
+    }
+
     /// Checks if the column is empty. Returns `true` if there are no elements, `false` otherwise.
     #[inline]
     pub fn is_empty(&self) -> bool {
@@ -300,6 +320,13 @@ impl Column {
         self.changed_ticks.reserve_exact(additional);
     }
 
+    /// Shrinks the capacity of the column as much as possible, down to its length.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.data.shrink_to_fit();
+        self.added_ticks.shrink_to_fit();
+        self.changed_ticks.shrink_to_fit();
+    }
+
     /// Fetches the data pointer to the first element of the [`Column`].
     ///
     /// The pointer is type erased, so using this function to fetch anything